@@ -0,0 +1,389 @@
+use super::*;
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StashScope {
+    #[default]
+    Workspace,
+    Monitor,
+    Everything,
+}
+
+impl StashScope {
+    pub fn matches(&self, instance: &StashedInstance) -> bool {
+        matches!(
+            (self, instance),
+            (Self::Workspace, StashedInstance::Workspace(_))
+                | (Self::Monitor, StashedInstance::Monitor(_))
+                | (Self::Everything, StashedInstance::Everything(_))
+        )
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub enum StashTarget {
+    Workspace(WorkspaceId),
+    Monitor(MonitorId),
+    Everything,
+}
+
+impl StashTarget {
+    pub fn active(data: &Data, scope: StashScope) -> Self {
+        match scope {
+            StashScope::Workspace => Self::Workspace(data.active_workspace),
+            StashScope::Monitor => Self::Monitor(data.active_monitor),
+            StashScope::Everything => Self::Everything,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Workspace(_) => "workspace",
+            Self::Monitor(_) => "monitor",
+            Self::Everything => "everything",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct PopOptions {
+    pub pop_mode: Option<PopMode>,
+    pub workspace_target: Option<WorkspaceId>,
+    pub monitor_target: Option<MonitorId>,
+    pub relative: bool,
+    pub exact: bool,
+    pub no_missing_monitors: bool,
+    pub monitor_match: MonitorMatch,
+    pub here: bool,
+    pub steal: bool,
+    pub force: bool,
+    pub relaunch: bool,
+    pub only: PopSelection,
+    pub conflict: ConflictPolicy,
+    pub stash_location: WorkspaceId,
+    pub collision: CollisionPolicy,
+    pub no_restore: HashSet<WindowProperty>,
+}
+
+// Restricts a pop to some workspaces or monitors of a monitor or session stash.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct PopSelection {
+    pub workspaces: Vec<WorkspaceId>,
+    pub monitors: Vec<String>,
+}
+
+impl PopSelection {
+    pub fn is_empty(&self) -> bool {
+        self.workspaces.is_empty() && self.monitors.is_empty()
+    }
+
+    pub fn matches_monitor(&self, monitor: &StashedMonitor) -> bool {
+        self.monitors.is_empty() || self.monitors.iter().any(|wanted| monitor.is_called(wanted))
+    }
+
+    pub fn matches_workspace(&self, workspace: &StashedWorkspace) -> bool {
+        self.workspaces.is_empty() || self.workspaces.contains(&workspace.original_workspace)
+    }
+}
+
+impl PopOptions {
+    pub fn from_policy(policy: &Policy) -> Self {
+        PopOptions {
+            no_missing_monitors: policy.no_missing_monitors.unwrap_or(false),
+            monitor_match: policy.monitor_match.unwrap_or_default(),
+            here: policy.here.unwrap_or(false),
+            steal: policy.steal.unwrap_or(false),
+            force: policy.force.unwrap_or(false),
+            conflict: policy.conflict.unwrap_or_default(),
+            stash_location: policy.stash_location(),
+            collision: policy.collision.unwrap_or_default(),
+            no_restore: policy.no_restore.iter().flatten().copied().collect(),
+            ..Default::default()
+        }
+        .with_pop_mode(policy.pop_mode)
+    }
+
+    fn with_pop_mode(self, pop_mode: Option<PopMode>) -> Self {
+        PopOptions {
+            pop_mode,
+            relative: pop_mode == Some(PopMode::Relative),
+            // Restoring brings windows back from wherever they are, onto their own workspaces.
+            exact: matches!(pop_mode, Some(PopMode::Exact | PopMode::Restore)),
+            steal: self.steal || pop_mode == Some(PopMode::Restore),
+            ..self
+        }
+    }
+}
+
+pub fn stash(
+    data: &Data,
+    name: &str,
+    target: StashTarget,
+    policy: &Policy,
+    meta: StashMeta,
+    config: &Config,
+) -> Result<StashReport> {
+    let hooks = &config.hooks;
+    StashedInstance::check_already_stashed(name)?;
+
+    hooks.run(
+        Hook::PreStash,
+        &HookContext {
+            name,
+            stash_type: target.kind(),
+            description: serde_json::to_string(&target)?,
+            extra_env: vec![],
+        },
+    )?;
+
+    // Nothing is moved onto the stash location of a kept stash, so it can't collide.
+    let keep = policy.keep.unwrap_or(false);
+    let meta = StashMeta { keep, ..meta };
+    let registry = StashRegistry::load()?;
+    let stash_location = if keep {
+        policy.stash_location()
+    } else {
+        registry.resolve_location(
+            data,
+            policy.stash_location(),
+            policy.collision.unwrap_or_default(),
+        )?
+    };
+    let mut excluded = registry.locations();
+    excluded.insert(stash_location);
+    debug!(name, target = ?target, stash_location, "stash");
+
+    let (instance, dispatch_error) = match target {
+        StashTarget::Workspace(workspace) if excluded.contains(&workspace) => {
+            return Err(StashError::StashingStashLocation(workspace).into());
+        }
+        StashTarget::Workspace(workspace) => {
+            let (instance, dispatch_error) =
+                workspace_stash(data, workspace, stash_location, keep)?;
+            (StashedInstance::Workspace(instance), dispatch_error)
+        }
+        StashTarget::Monitor(monitor) => {
+            let (instance, dispatch_error) =
+                monitor_stash(data, monitor, stash_location, &excluded, keep)?;
+            (StashedInstance::Monitor(instance), dispatch_error)
+        }
+        StashTarget::Everything => {
+            let (instance, dispatch_error) =
+                everything_stash(data, stash_location, &excluded, keep)?;
+            (StashedInstance::Everything(instance), dispatch_error)
+        }
+    };
+    let context = HookContext {
+        name,
+        stash_type: instance.kind(),
+        description: serde_json::to_string(&instance)?,
+        extra_env: vec![],
+    };
+    let mut report = StashReport {
+        name: name.to_owned(),
+        kind: instance.kind().to_owned(),
+        stash_location,
+        windows: instance.client_addresses().into_iter().cloned().collect(),
+        workspaces: instance
+            .stashed_workspaces()
+            .iter()
+            .map(|workspace| workspace.original_workspace)
+            .collect(),
+        errors: vec![],
+    };
+    let summary = format!(
+        "{} {} windows as '{}'",
+        if keep { "Recorded" } else { "Stashed" },
+        report.windows.len(),
+        name
+    );
+    instance.write_with_meta(name, meta)?;
+
+    if let Some(errors) = dispatch_error {
+        config.notify.send(
+            Urgency::Warning,
+            &format!("{}, with {} dispatch errors", summary, errors.len()),
+        );
+        report.errors = errors.messages();
+    } else {
+        config.notify.send(Urgency::Info, &summary);
+    }
+
+    hooks.run(Hook::OnStash, &context)?;
+    Ok(report)
+}
+
+pub fn pop(
+    data: &Data,
+    name: &str,
+    instance: StashedInstance,
+    options: &PopOptions,
+    config: &Config,
+) -> Result<PopReport> {
+    let hooks = &config.hooks;
+    let context = HookContext {
+        name,
+        stash_type: instance.kind(),
+        description: serde_json::to_string(&instance)?,
+        extra_env: vec![],
+    };
+    hooks.run(Hook::PrePop, &context)?;
+
+    // Kept stashes left their windows in use, so any mode has to take them from where they are.
+    let kept = StashedInstance::read_with_meta(name).is_ok_and(|(_, meta)| meta.keep);
+    let options = &PopOptions {
+        steal: options.steal || kept,
+        ..options.clone()
+    };
+
+    let selected = instance.select(&options.only)?;
+    debug!(
+        name,
+        kind = instance.kind(),
+        workspaces = selected
+            .stashed_workspaces()
+            .iter()
+            .map(|workspace| workspace.original_workspace.to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+        options = ?options,
+        "pop"
+    );
+    if let StashedInstance::Everything(stashed_session) = &selected {
+        let missing_monitors = stashed_session.missing_monitors(data, options.monitor_match);
+        if !missing_monitors.is_empty() {
+            let missing_monitors = missing_monitors
+                .iter()
+                .map(|monitor| monitor.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            debug!(monitors = missing_monitors, "missing_monitors");
+            hooks.run(
+                Hook::OnPopMissingMonitor,
+                &HookContext {
+                    description: context.description.clone(),
+                    extra_env: vec![("HYPRSTASH_MISSING_MONITORS", missing_monitors)],
+                    ..context
+                },
+            )?;
+        }
+    }
+
+    let mut conflicts = Conflicts {
+        policy: options.conflict,
+        stash_location: options.stash_location,
+        collision: options.collision,
+        taken: selected
+            .stashed_workspaces()
+            .iter()
+            .map(|workspace| workspace.original_workspace)
+            .collect(),
+        stashed: selected.client_addresses().into_iter().cloned().collect(),
+        ..Default::default()
+    };
+    let mut report = match &selected {
+        _ if options.relaunch => {
+            let target = options
+                .workspace_target
+                .or(options.here.then_some(data.active_workspace));
+            relaunch_pop(&selected, target)?
+        }
+        StashedInstance::Workspace(stashed_workspace) => {
+            let target = options
+                .workspace_target
+                .or(options.here.then_some(data.active_workspace));
+            workspace_pop(
+                data,
+                stashed_workspace,
+                target,
+                options.steal,
+                &mut conflicts,
+            )?
+        }
+        StashedInstance::Monitor(stashed_monitor) => {
+            if options.here {
+                monitor_pop_absolute(
+                    data,
+                    stashed_monitor,
+                    Some(options.monitor_target.unwrap_or(data.active_monitor)),
+                    Some(data.active_workspace),
+                    options.steal,
+                    options.monitor_match,
+                    &mut conflicts,
+                )?
+            } else if options.exact {
+                monitor_pop_exact(
+                    data,
+                    stashed_monitor,
+                    options.monitor_target,
+                    options.steal,
+                    options.monitor_match,
+                    &mut conflicts,
+                )?
+            } else if options.relative {
+                let target = options
+                    .monitor_target
+                    .or_else(|| stashed_monitor.resolve_monitor(data, options.monitor_match));
+                monitor_pop_relative(
+                    data,
+                    stashed_monitor,
+                    target,
+                    false,
+                    options.steal,
+                    &mut conflicts,
+                )?
+            } else {
+                monitor_pop_absolute(
+                    data,
+                    stashed_monitor,
+                    options.monitor_target,
+                    None,
+                    options.steal,
+                    options.monitor_match,
+                    &mut conflicts,
+                )?
+            }
+        }
+        StashedInstance::Everything(stashed_session) => {
+            everything_pop(data, stashed_session, options, &mut conflicts)?
+        }
+    };
+    report.warnings.append(&mut conflicts.warnings);
+    for (old, new) in report.workspaces.iter() {
+        debug!(stashed = old, popped = new, "workspace_mapping");
+    }
+    debug!(
+        restored = report.restored.len(),
+        moved = report.moved.len(),
+        missing = report.missing.len(),
+        "popped"
+    );
+    // Relaunched programs are new windows, there's nothing to restore onto.
+    if !options.relaunch {
+        let clients = selected
+            .stashed_workspaces()
+            .into_iter()
+            .flat_map(|workspace| workspace.clients.iter())
+            .filter(|client| report.restored.contains(&client.address))
+            .collect::<Vec<_>>();
+        if let Some(errors) = restore_window_states(data, &clients, &options.no_restore) {
+            report.errors.extend(errors.messages());
+        }
+    }
+    let urgency = if report.is_partial() {
+        Urgency::Warning
+    } else {
+        Urgency::Info
+    };
+    config.notify.send(urgency, &report.summary(name));
+    report.name = name.to_owned();
+    report.kind = instance.kind().to_owned();
+    // A restored bookmark stays around, to restore again later.
+    if !kept || options.pop_mode != Some(PopMode::Restore) {
+        instance.finish_pop(name, &mut report, options.force, &options.only)?;
+    }
+
+    hooks.run(Hook::OnPop, &context)?;
+    Ok(report)
+}
@@ -0,0 +1,108 @@
+use super::*;
+
+use clap_complete::{
+    CompletionCandidate,
+    env::{Bash, EnvCompleter, Fish, Zsh},
+};
+use std::{ffi::OsStr, io};
+
+// Shells call back into `COMPLETE=<shell> hyprstash -- <args>`, which clap_complete answers
+// before anything else runs.
+pub const COMPLETE_ENV: &str = "COMPLETE";
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    fn completer(self) -> &'static dyn EnvCompleter {
+        match self {
+            Self::Bash => &Bash,
+            Self::Zsh => &Zsh,
+            Self::Fish => &Fish,
+        }
+    }
+}
+
+pub fn print_completions(shell: Shell, name: &str) -> Result<()> {
+    shell
+        .completer()
+        .write_registration(COMPLETE_ENV, name, name, name, &mut io::stdout())?;
+    Ok(())
+}
+
+fn candidates(current: &OsStr, values: Vec<(String, Option<String>)>) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+    values
+        .into_iter()
+        .filter(|(value, _)| value.starts_with(current.as_ref()))
+        .map(|(value, help)| CompletionCandidate::new(value).help(help.map(Into::into)))
+        .collect()
+}
+
+// Completion runs before the config is loaded, so the store has to be found here.
+pub fn complete_names(current: &OsStr) -> Vec<CompletionCandidate> {
+    let store_path = Config::load_config(None)
+        .ok()
+        .and_then(|config| config.store_path)
+        .map(PathBuf::from);
+    let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE").ok();
+    StashedInstance::set_store_path(store_path, signature.as_deref());
+
+    let mut names = StashedInstance::list_instances().unwrap_or_default();
+    names.sort();
+    candidates(
+        current,
+        names.into_iter().map(|name| (name, None)).collect(),
+    )
+}
+
+pub fn complete_workspaces(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Ok(data) = Data::new() else {
+        return vec![];
+    };
+    let mut workspaces = data
+        .workspaces
+        .iter()
+        .filter(|workspace| workspace.id > 0)
+        .map(|workspace| (workspace.id, workspace.monitor.clone()))
+        .collect::<Vec<_>>();
+    workspaces.sort();
+    candidates(
+        current,
+        workspaces
+            .into_iter()
+            .map(|(id, monitor)| (id.to_string(), Some(monitor)))
+            .collect(),
+    )
+}
+
+pub fn complete_monitor_ids(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Ok(data) = Data::new() else {
+        return vec![];
+    };
+    candidates(
+        current,
+        data.monitors
+            .iter()
+            .map(|monitor| (monitor.id.to_string(), Some(monitor.name.clone())))
+            .collect(),
+    )
+}
+
+// `--only-monitor` also takes names, which survive reconnecting displays.
+pub fn complete_monitor_names(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Ok(data) = Data::new() else {
+        return vec![];
+    };
+    candidates(
+        current,
+        data.monitors
+            .iter()
+            .map(|monitor| (monitor.name.clone(), Some(monitor.description.clone())))
+            .collect(),
+    )
+}
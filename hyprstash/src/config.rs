@@ -0,0 +1,178 @@
+use super::*;
+use std::{env, fs, path::PathBuf, time::Duration};
+
+const CONFIG_NAME: &str = "hypr/hyprstash";
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PopMode {
+    Absolute,
+    Relative,
+    Exact,
+    Restore,
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// Add the stashed windows to whatever is on the workspace now
+    #[default]
+    Merge,
+    /// Stash the workspace's windows onto the stack first
+    Swap,
+    /// Pop onto the next empty workspace instead
+    NextFree,
+    /// Refuse to pop
+    Fail,
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MonitorMatch {
+    #[default]
+    Id,
+    Name,
+    Description,
+}
+
+// Every field is optional so that policies can be layered on top of each other.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct Policy {
+    pub stash_location: Option<WorkspaceId>,
+    pub collision: Option<CollisionPolicy>,
+    pub keep: Option<bool>,
+    pub pop_mode: Option<PopMode>,
+    pub conflict: Option<ConflictPolicy>,
+    pub monitor_match: Option<MonitorMatch>,
+    pub no_missing_monitors: Option<bool>,
+    pub here: Option<bool>,
+    pub steal: Option<bool>,
+    pub force: Option<bool>,
+    pub no_restore: Option<Vec<WindowProperty>>,
+    pub expire: Option<String>,
+}
+
+impl Policy {
+    pub fn merge(&self, over: &Policy) -> Policy {
+        Policy {
+            stash_location: over.stash_location.or(self.stash_location),
+            collision: over.collision.or(self.collision),
+            keep: over.keep.or(self.keep),
+            pop_mode: over.pop_mode.or(self.pop_mode),
+            conflict: over.conflict.or(self.conflict),
+            monitor_match: over.monitor_match.or(self.monitor_match),
+            no_missing_monitors: over.no_missing_monitors.or(self.no_missing_monitors),
+            here: over.here.or(self.here),
+            steal: over.steal.or(self.steal),
+            force: over.force.or(self.force),
+            no_restore: over.no_restore.clone().or(self.no_restore.clone()),
+            expire: over.expire.clone().or(self.expire.clone()),
+        }
+    }
+
+    pub fn expire(&self) -> Result<Option<Duration>> {
+        let Some(expire) = self.expire.as_ref() else {
+            return Ok(None);
+        };
+        let expire = parse_duration(expire).map_err(StashError::BadDuration)?;
+        Ok(Some(expire))
+    }
+
+    pub fn stash_location(&self) -> WorkspaceId {
+        self.stash_location.unwrap_or(DEFAULT_STASH_LOCATION)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub store_path: Option<String>,
+    pub notify: NotifySink,
+    pub log_file: bool,
+    pub gc: GcPolicy,
+    // How often a running daemon collects garbage, never if unset.
+    pub gc_interval: Option<String>,
+    pub hooks: Hooks,
+    pub defaults: Policy,
+    pub stashes: HashMap<String, Policy>,
+}
+
+impl Config {
+    fn resolve_config_dir() -> Result<PathBuf> {
+        if let Some(config_home) = env::var_os("XDG_CONFIG_HOME")
+            && !config_home.is_empty()
+        {
+            return Ok(PathBuf::from(config_home));
+        }
+        let home_dir = env::home_dir().ok_or(StashError::NoHomeDir)?;
+        Ok(home_dir.join(".config"))
+    }
+
+    // JSON wins when both exist, so adding a TOML config never breaks a working setup.
+    pub fn resolve_config_path(config_path: Option<String>) -> Result<(PathBuf, bool)> {
+        if let Some(path) = config_path {
+            return Ok((PathBuf::from(path), true));
+        }
+
+        let config_dir = Self::resolve_config_dir()?;
+        let json_path = config_dir.join(format!("{}.json", CONFIG_NAME));
+        let toml_path = config_dir.join(format!("{}.toml", CONFIG_NAME));
+        if !fs::exists(&json_path)? && fs::exists(&toml_path)? {
+            return Ok((toml_path, false));
+        }
+
+        Ok((json_path, false))
+    }
+
+    // A missing config is only an error if it was explicitly asked for.
+    pub fn load_config(config_path: Option<String>) -> Result<Config> {
+        let (config_path, explicit) = Self::resolve_config_path(config_path)?;
+
+        if !explicit && !fs::exists(&config_path)? {
+            return Ok(Config::default());
+        }
+        let toml_path = config_path.with_extension("toml");
+        if !explicit && toml_path != config_path && fs::exists(&toml_path)? {
+            eprintln!(
+                "Ignoring {} since {} exists",
+                toml_path.display(),
+                config_path.display()
+            );
+        }
+
+        let data = fs::read_to_string(&config_path)?;
+        let config = if config_path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&data)?
+        } else {
+            serde_json::from_str(&data)?
+        };
+
+        Ok(config)
+    }
+
+    pub fn gc_interval(&self) -> Result<Option<Duration>> {
+        let Some(interval) = self.gc_interval.as_ref() else {
+            return Ok(None);
+        };
+        let interval = parse_duration(interval).map_err(StashError::BadDuration)?;
+        Ok(Some(interval))
+    }
+
+    pub fn policy(&self, name: &str) -> Policy {
+        let policy = match self.stashes.get(name) {
+            Some(policy) => self.defaults.merge(policy),
+            None => self.defaults.clone(),
+        };
+        // Kept stashes only record where everything is, so they're restored unless a flag
+        // asks for another mode, whatever mode the config prefers.
+        if StashedInstance::read_with_meta(name).is_ok_and(|(_, meta)| meta.keep) {
+            policy.merge(&Policy {
+                pop_mode: Some(PopMode::Restore),
+                ..Default::default()
+            })
+        } else {
+            policy
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use super::*;
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+};
+
+// The control socket speaks newline delimited JSON: one request per line, answered by
+// one response per line. Subscribers receive a `changed` event whenever the store does.
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Method {
+    Stash,
+    // Stash under a generated name on top of the stack.
+    Push,
+    Pop,
+    PopAll,
+    #[default]
+    List,
+    Show,
+    Gc,
+    Subscribe,
+}
+
+// Kept flat rather than as a tagged enum, serde can't buffer the i128 monitor ids.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Request {
+    pub method: Method,
+    // Pops without a name pop the top of the stack.
+    pub name: Option<String>,
+    // What to stash, or for pops the kind of stash expected.
+    pub scope: Option<StashScope>,
+    pub workspace: Option<WorkspaceId>,
+    pub monitor: Option<MonitorId>,
+    pub policy: Policy,
+    pub tags: Vec<String>,
+    pub note: Option<String>,
+    pub only: PopSelection,
+    pub gc: Option<GcPolicy>,
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // Lets clients exit the same way they would have without the daemon.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ExitKind>,
+}
+
+impl Response {
+    pub fn from_result(result: Result<serde_json::Value>) -> Self {
+        match result {
+            Ok(result) => Response {
+                ok: true,
+                result: Some(result),
+                error: None,
+                status: None,
+            },
+            Err(error) => Response {
+                ok: false,
+                result: None,
+                error: Some(error.to_string()),
+                status: Some(ExitKind::from_error(&error)),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChangedEvent {
+    pub event: &'static str,
+    pub stashes: Vec<String>,
+}
+
+impl ChangedEvent {
+    pub fn new() -> Result<Self> {
+        let mut stashes = StashedInstance::list_instances()?;
+        stashes.sort();
+        Ok(ChangedEvent {
+            event: "changed",
+            stashes,
+        })
+    }
+}
+
+pub fn control_socket_path() -> Result<PathBuf> {
+    Ok(hypr_instance_dir()?.join(".hyprstash.sock"))
+}
+
+// Returns `None` when no daemon is running, so callers can fall back to doing the work
+// themselves.
+pub fn daemon_request(request: &Request) -> Result<Option<serde_json::Value>> {
+    let Ok(mut stream) = UnixStream::connect(control_socket_path()?) else {
+        return Ok(None);
+    };
+    writeln!(stream, "{}", serde_json::to_string(request)?)?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let response = serde_json::from_str::<Response>(&line)?;
+    if response.ok {
+        Ok(Some(response.result.unwrap_or_default()))
+    } else {
+        Err(StashError::Daemon(
+            response.error.unwrap_or_default(),
+            response.status.unwrap_or(ExitKind::Failure),
+        )
+        .into())
+    }
+}
@@ -0,0 +1,294 @@
+use super::*;
+
+use hyprland::event_listener::EventListener;
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Sender},
+    },
+    thread,
+    time::Duration,
+};
+
+const STORE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+// Events come in bursts, e.g. a workspace switch moves focus too.
+const REFRESH_DEBOUNCE: Duration = Duration::from_millis(50);
+// Subscribers that stop reading are dropped rather than stalling every reply.
+const SUBSCRIBER_WRITE_TIMEOUT: Duration = Duration::from_millis(200);
+
+struct Daemon {
+    config: Config,
+    // Refreshed after compositor events, so requests don't have to query everything
+    // again. Holding the lock also serializes stashes and pops.
+    data: Mutex<Option<Data>>,
+    // Without events the cache can't be trusted.
+    listening: AtomicBool,
+    refresh: Sender<()>,
+    subscribers: Mutex<Vec<UnixStream>>,
+}
+
+impl Daemon {
+    fn handle(&self, request: Request) -> Result<serde_json::Value> {
+        debug!(
+            method = ?request.method,
+            name = request.name.as_deref().unwrap_or_default(),
+            "request"
+        );
+        match request.method {
+            Method::List => Ok(serde_json::to_value(list_by_tags(&request.tags)?)?),
+            Method::Show => {
+                let name = request.name.ok_or(StashError::MissingRequestName)?;
+                StashedInstance::read_as_json(&name)
+            }
+            Method::Stash | Method::Push => {
+                let report = self.with_data(|data| {
+                    // Named under the lock, so concurrent pushes don't pick the same name.
+                    let name = match request.method {
+                        Method::Push => next_stack_name()?,
+                        _ => request.name.clone().ok_or(StashError::MissingRequestName)?,
+                    };
+                    let policy = self.config.policy(&name).merge(&request.policy);
+                    let meta = StashMeta {
+                        tags: request.tags.clone(),
+                        note: request.note.clone(),
+                        ..StashMeta::new(policy.expire()?)?
+                    };
+                    let target = match request.scope.unwrap_or(StashScope::Workspace) {
+                        StashScope::Workspace => StashTarget::Workspace(
+                            request.workspace.unwrap_or(data.active_workspace),
+                        ),
+                        StashScope::Monitor => {
+                            StashTarget::Monitor(request.monitor.unwrap_or(data.active_monitor))
+                        }
+                        StashScope::Everything => StashTarget::Everything,
+                    };
+                    stash(data, &name, target, &policy, meta, &self.config)
+                })?;
+                self.broadcast();
+                Ok(serde_json::to_value(report)?)
+            }
+            Method::Pop => {
+                let report = self.with_data(|data| {
+                    let name = match request.name.clone() {
+                        Some(name) => name,
+                        None => stack_top()?,
+                    };
+                    let instance = StashedInstance::new_from_name(&name)?;
+                    if let Some(scope) = request.scope
+                        && !scope.matches(&instance)
+                    {
+                        return Err(StashError::MismatchedPopType.into());
+                    }
+                    let options = PopOptions {
+                        workspace_target: request.workspace,
+                        monitor_target: request.monitor,
+                        only: request.only.clone(),
+                        ..PopOptions::from_policy(&self.config.policy(&name).merge(&request.policy))
+                    };
+                    pop(data, &name, instance, &options, &self.config)
+                })?;
+                self.broadcast();
+                Ok(serde_json::to_value(report)?)
+            }
+            Method::PopAll => {
+                // Takes its own snapshot before every pop.
+                let outcome = self.exclusive(|| pop_all(&request.policy, &self.config))?;
+                self.broadcast();
+                Ok(serde_json::to_value(outcome)?)
+            }
+            Method::Gc => {
+                let policy = request.gc.unwrap_or(self.config.gc);
+                let outcome = self.with_data(|data| {
+                    collect_garbage(data, policy, request.dry_run, &self.config)
+                })?;
+                self.broadcast();
+                Ok(serde_json::to_value(outcome)?)
+            }
+            Method::Subscribe => unreachable!(),
+        }
+    }
+
+    // The snapshot is always dropped afterwards since we just changed the compositor state.
+    fn with_data<T>(&self, action: impl FnOnce(&Data) -> Result<T>) -> Result<T> {
+        let mut data = self.data.lock().unwrap();
+        let res = match data.take() {
+            Some(cached) if self.listening.load(Ordering::Relaxed) => action(&cached),
+            _ => action(&Data::new()?),
+        };
+        self.invalidate();
+        res
+    }
+
+    // For work that snapshots the compositor state by itself.
+    fn exclusive<T>(&self, action: impl FnOnce() -> Result<T>) -> Result<T> {
+        let mut data = self.data.lock().unwrap();
+        data.take();
+        let res = action();
+        self.invalidate();
+        res
+    }
+
+    fn invalidate(&self) {
+        let _ = self.refresh.send(());
+    }
+
+    fn broadcast(&self) {
+        let Ok(event) = ChangedEvent::new().and_then(|event| Ok(serde_json::to_string(&event)?))
+        else {
+            return;
+        };
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain_mut(|subscriber| writeln!(subscriber, "{}", event).is_ok());
+    }
+
+    fn serve(&self, stream: UnixStream) -> Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let request = serde_json::from_str::<Request>(&line);
+            if let Ok(Request {
+                method: Method::Subscribe,
+                ..
+            }) = request
+            {
+                writeln!(
+                    writer,
+                    "{}",
+                    serde_json::to_string(&Response::from_result(Ok(serde_json::Value::Null)))?
+                )?;
+                writer.set_write_timeout(Some(SUBSCRIBER_WRITE_TIMEOUT))?;
+                self.subscribers.lock().unwrap().push(writer);
+                return Ok(());
+            }
+
+            let response = Response::from_result(
+                request
+                    .map_err(anyhow::Error::from)
+                    .and_then(|request| self.handle(request)),
+            );
+            writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn run_daemon(config: Config) -> Result<()> {
+    let path = control_socket_path()?;
+    // A stale socket from a daemon that died refuses connections.
+    if UnixStream::connect(&path).is_ok() {
+        return Err(StashError::DaemonRunning.into());
+    }
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+
+    let (refresh, refresh_rx) = mpsc::channel();
+    let daemon = Arc::new(Daemon {
+        config,
+        data: Mutex::new(None),
+        listening: AtomicBool::new(true),
+        refresh,
+        subscribers: Mutex::new(vec![]),
+    });
+
+    let refresher = daemon.clone();
+    thread::spawn(move || {
+        while refresh_rx.recv().is_ok() {
+            *refresher.data.lock().unwrap() = None;
+            thread::sleep(REFRESH_DEBOUNCE);
+            while refresh_rx.try_recv().is_ok() {}
+            // Snapshot under the lock, so a stash or pop can't sneak in before it is stored.
+            let mut data = refresher.data.lock().unwrap();
+            *data = Data::new().ok();
+        }
+    });
+    daemon.invalidate();
+
+    let events = daemon.clone();
+    thread::spawn(move || {
+        let mut listener = EventListener::new();
+        macro_rules! invalidate_on {
+            ($($handler:ident),*) => {
+                $(
+                    let daemon = events.clone();
+                    listener.$handler(move |_| daemon.invalidate());
+                )*
+            };
+        }
+        // Window states are restored with toggles, and workspace rules come from the config,
+        // so a stale snapshot would get those wrong too.
+        invalidate_on!(
+            add_window_opened_handler,
+            add_window_closed_handler,
+            add_window_moved_handler,
+            add_workspace_changed_handler,
+            add_workspace_added_handler,
+            add_workspace_deleted_handler,
+            add_workspace_moved_handler,
+            add_active_monitor_changed_handler,
+            add_monitor_added_handler,
+            add_monitor_removed_handler,
+            add_active_window_changed_handler,
+            add_window_pinned_handler,
+            add_float_state_changed_handler,
+            add_fullscreen_state_changed_handler
+        );
+        let daemon = events.clone();
+        listener.add_config_reloaded_handler(move || daemon.invalidate());
+        let _ = listener.start_listener();
+        events.listening.store(false, Ordering::Relaxed);
+    });
+
+    // Stashes can also change behind our back, e.g. by the CLI in direct mode.
+    let watcher = daemon.clone();
+    thread::spawn(move || {
+        let mut last = StashedInstance::store_fingerprint();
+        loop {
+            thread::sleep(STORE_POLL_INTERVAL);
+            let current = StashedInstance::store_fingerprint();
+            if current != last {
+                last = current;
+                watcher.broadcast();
+            }
+        }
+    });
+
+    if let Some(interval) = daemon.config.gc_interval()? {
+        let collector = daemon.clone();
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+                let res = collector.with_data(|data| {
+                    collect_garbage(data, collector.config.gc, false, &collector.config)
+                });
+                if let Err(error) = res {
+                    eprintln!("Periodic gc failed: {}", error);
+                }
+                collector.broadcast();
+            }
+        });
+    }
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let daemon = daemon.clone();
+        thread::spawn(move || {
+            if let Err(error) = daemon.serve(stream) {
+                eprintln!("Control connection failed: {}", error);
+            }
+        });
+    }
+
+    Ok(())
+}
@@ -1,38 +1,206 @@
 use super::*;
 
 use hyprland::data::*;
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+const SNAPSHOT_ATTEMPTS: usize = 5;
+const SNAPSHOT_RETRY_DELAY: Duration = Duration::from_millis(20);
 
 #[derive(Debug)]
 pub struct Data {
-    pub monitors: Monitors,
-    pub clients: Clients,
-    pub workspaces: Workspaces,
+    pub monitors: Vec<Monitor>,
+    pub clients: Vec<Client>,
+    pub workspaces: Vec<Workspace>,
+    // Kept as plain JSON, the hyprland crate doesn't know every rule option.
+    pub workspace_rules: Vec<serde_json::Value>,
 
     pub active_workspace: WorkspaceId,
     pub active_monitor: MonitorId,
+
+    clients_by_address: HashMap<Address, usize>,
+    clients_by_workspace: HashMap<WorkspaceId, Vec<usize>>,
+    workspaces_by_monitor: HashMap<MonitorId, Vec<WorkspaceId>>,
 }
 
 impl Data {
     pub fn new() -> Result<Self> {
-        let (active_monitor, active_workspace) = Monitors::get()?
-            .into_iter()
-            .find_map(|monitor| {
-                monitor
-                    .focused
-                    .then_some((monitor.id, monitor.active_workspace.id))
-            })
+        let mut last_error = None;
+        for attempt in 1..=SNAPSHOT_ATTEMPTS {
+            let start = Instant::now();
+            match Self::snapshot() {
+                Ok(data) => {
+                    debug!(
+                        monitors = data.monitors.len(),
+                        clients = data.clients.len(),
+                        workspaces = data.workspaces.len(),
+                        active_workspace = data.active_workspace,
+                        active_monitor = data.active_monitor,
+                        elapsed_us = start.elapsed().as_micros(),
+                        "snapshot"
+                    );
+                    return Ok(data);
+                }
+                Err(error) => {
+                    debug!(attempt, error = %error, "snapshot_retry");
+                    last_error = Some(error);
+                }
+            }
+            thread::sleep(SNAPSHOT_RETRY_DELAY);
+        }
+
+        Err(last_error.unwrap())
+    }
+
+    fn snapshot() -> Result<Self> {
+        let mut values = batch_request(&[
+            "j/monitors",
+            "j/clients",
+            "j/workspaces",
+            "j/activeworkspace",
+            "j/workspacerules",
+        ])?
+        .into_iter();
+
+        let monitors: Vec<Monitor> = serde_json::from_value(values.next().unwrap())?;
+        let clients: Vec<Client> = serde_json::from_value(values.next().unwrap())?;
+        let workspaces: Vec<Workspace> = serde_json::from_value(values.next().unwrap())?;
+        let active: Workspace = serde_json::from_value(values.next().unwrap())?;
+        let workspace_rules: Vec<serde_json::Value> =
+            serde_json::from_value(values.next().unwrap())?;
+
+        let active_workspace = active.id;
+        let active_monitor = active
+            .monitor_id
             .ok_or(StashError::NoActiveMonitorWorkspace)?;
 
-        let monitors = Monitors::get()?;
-        let clients = Clients::get()?;
-        let workspaces = Workspaces::get()?;
+        let data = Self::with_indexes(
+            monitors,
+            clients,
+            workspaces,
+            workspace_rules,
+            active_workspace,
+            active_monitor,
+        );
+        data.validate()?;
+        Ok(data)
+    }
 
-        Ok(Data {
+    fn with_indexes(
+        monitors: Vec<Monitor>,
+        clients: Vec<Client>,
+        workspaces: Vec<Workspace>,
+        workspace_rules: Vec<serde_json::Value>,
+        active_workspace: WorkspaceId,
+        active_monitor: MonitorId,
+    ) -> Self {
+        let mut clients_by_address = HashMap::new();
+        let mut clients_by_workspace = HashMap::<_, Vec<_>>::new();
+        for (idx, client) in clients.iter().enumerate() {
+            clients_by_address.insert(client.address.clone(), idx);
+            clients_by_workspace
+                .entry(client.workspace.id)
+                .or_default()
+                .push(idx);
+        }
+
+        let mut workspaces_by_monitor = HashMap::<_, Vec<_>>::new();
+        for workspace in workspaces.iter() {
+            if let Some(monitor) = workspace.monitor_id {
+                workspaces_by_monitor
+                    .entry(monitor)
+                    .or_default()
+                    .push(workspace.id);
+            }
+        }
+
+        Data {
             monitors,
             clients,
             workspaces,
+            workspace_rules,
             active_workspace,
             active_monitor,
-        })
+            clients_by_address,
+            clients_by_workspace,
+            workspaces_by_monitor,
+        }
+    }
+
+    // Even a batched request can race with windows and workspaces being created,
+    // so make sure everything refers to something that exists.
+    fn validate(&self) -> Result<()> {
+        if self.monitor(self.active_monitor).is_none() || !self.has_workspace(self.active_workspace)
+        {
+            return Err(StashError::InconsistentSnapshot.into());
+        }
+
+        let orphaned_client = self
+            .clients
+            .iter()
+            .any(|client| client.mapped && !self.has_workspace(client.workspace.id));
+        let orphaned_workspace = self.workspaces.iter().any(|workspace| {
+            workspace
+                .monitor_id
+                .is_some_and(|monitor| self.monitor(monitor).is_none())
+        });
+        if orphaned_client || orphaned_workspace {
+            return Err(StashError::InconsistentSnapshot.into());
+        }
+
+        Ok(())
+    }
+
+    pub fn client(&self, address: &Address) -> Option<&Client> {
+        self.clients_by_address
+            .get(address)
+            .map(|idx| &self.clients[*idx])
+    }
+
+    pub fn clients_on(&self, workspace: WorkspaceId) -> impl Iterator<Item = &Client> {
+        self.clients_by_workspace
+            .get(&workspace)
+            .into_iter()
+            .flatten()
+            .map(|idx| &self.clients[*idx])
+    }
+
+    pub fn workspaces_on(&self, monitor: MonitorId) -> &[WorkspaceId] {
+        self.workspaces_by_monitor
+            .get(&monitor)
+            .map(|workspaces| workspaces.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn workspace_rules_for(&self, workspace: WorkspaceId) -> Vec<&serde_json::Value> {
+        let name = self
+            .workspaces
+            .iter()
+            .find(|w| w.id == workspace)
+            .map(|w| format!("name:{}", w.name));
+        self.workspace_rules
+            .iter()
+            .filter(|rule| {
+                let selector = rule["workspaceString"].as_str();
+                selector == Some(&workspace.to_string()) || selector == name.as_deref()
+            })
+            .collect()
+    }
+
+    pub fn has_workspace(&self, workspace: WorkspaceId) -> bool {
+        self.workspaces.iter().any(|w| w.id == workspace)
+    }
+
+    pub fn workspace_monitor(&self, workspace: WorkspaceId) -> Option<MonitorId> {
+        self.workspaces
+            .iter()
+            .find(|w| w.id == workspace)
+            .and_then(|w| w.monitor_id)
+    }
+
+    pub fn monitor(&self, monitor: MonitorId) -> Option<&Monitor> {
+        self.monitors.iter().find(|m| m.id == monitor)
     }
 }
@@ -0,0 +1,194 @@
+use super::*;
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum WindowStatus {
+    // Still on the stash workspace, a pop brings it back.
+    Stashed,
+    // Still on its original workspace, as the windows of kept stashes are.
+    InPlace,
+    // Moved out of the stash workspace, only a pop with `--steal` brings it back.
+    Moved,
+    Closed,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WindowDiff {
+    pub address: Address,
+    pub class: String,
+    pub title: String,
+    pub status: WindowStatus,
+    pub workspace: Option<WorkspaceId>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WorkspaceDiff {
+    pub workspace: WorkspaceId,
+    // Windows on the original workspace by now that the stash doesn't know about.
+    pub others: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MonitorDiff {
+    pub monitor: MonitorId,
+    pub name: Option<String>,
+    // Where the monitor's workspaces would go, `None` if it isn't connected.
+    pub target: Option<MonitorId>,
+    // Session pops of missing monitors fall back to a relative pop onto the stashed id.
+    pub relative: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct StashDiff {
+    pub name: String,
+    pub kind: &'static str,
+    pub windows: Vec<WindowDiff>,
+    pub workspaces: Vec<WorkspaceDiff>,
+    pub monitors: Vec<MonitorDiff>,
+}
+
+fn describe_client(class: &str, title: &str) -> String {
+    format!("{} — {}", class, title)
+}
+
+impl StashDiff {
+    pub fn new(data: &Data, name: &str, instance: &StashedInstance, policy: &Policy) -> Self {
+        let stashed = instance.stashed_workspaces();
+        let addresses = instance
+            .client_addresses()
+            .into_iter()
+            .collect::<HashSet<_>>();
+
+        let mut windows = vec![];
+        let mut workspaces = vec![];
+        for workspace in stashed.iter() {
+            for address in workspace.client_addresses.iter() {
+                let stashed_client = workspace
+                    .clients
+                    .iter()
+                    .find(|client| &client.address == address);
+                let client = data.client(address);
+                let (status, current) = match client {
+                    Some(client) if client.workspace.id == workspace.stash_location => {
+                        (WindowStatus::Stashed, Some(client.workspace.id))
+                    }
+                    Some(client) if client.workspace.id == workspace.original_workspace => {
+                        (WindowStatus::InPlace, Some(client.workspace.id))
+                    }
+                    Some(client) => (WindowStatus::Moved, Some(client.workspace.id)),
+                    None => (WindowStatus::Closed, None),
+                };
+                // Stashes from before clients were recorded only know addresses.
+                let (class, title) = match (stashed_client, client) {
+                    (Some(stashed), _) => (stashed.class.clone(), stashed.title.clone()),
+                    (None, Some(client)) => (client.class.clone(), client.title.clone()),
+                    (None, None) => (String::new(), String::new()),
+                };
+                windows.push(WindowDiff {
+                    address: address.clone(),
+                    class,
+                    title,
+                    status,
+                    workspace: current,
+                });
+            }
+
+            let others = data
+                .clients_on(workspace.original_workspace)
+                .filter(|client| !addresses.contains(&client.address))
+                .map(|client| describe_client(&client.class, &client.title))
+                .collect();
+            workspaces.push(WorkspaceDiff {
+                workspace: workspace.original_workspace,
+                others,
+            });
+        }
+
+        let monitor_match = policy.monitor_match.unwrap_or_default();
+        let relative = policy.pop_mode == Some(PopMode::Relative);
+        let session = matches!(instance, StashedInstance::Everything(_));
+        let stashed_monitors = match instance {
+            StashedInstance::Workspace(_) => vec![],
+            StashedInstance::Monitor(monitor) => vec![monitor],
+            StashedInstance::Everything(session) => session.monitors.iter().collect(),
+        };
+        let monitors = stashed_monitors
+            .into_iter()
+            .map(|monitor| {
+                let target = monitor.resolve_monitor(data, monitor_match);
+                MonitorDiff {
+                    monitor: monitor.original_monitor,
+                    name: monitor.original_monitor_name.clone(),
+                    target,
+                    relative: relative || (session && target.is_none()),
+                }
+            })
+            .collect();
+
+        StashDiff {
+            name: name.to_owned(),
+            kind: instance.kind(),
+            windows,
+            workspaces,
+            monitors,
+        }
+    }
+
+    pub fn print(&self) {
+        println!("{} ({})", self.name, self.kind);
+
+        println!("Windows:");
+        for window in self.windows.iter() {
+            let status = match (&window.status, window.workspace) {
+                (WindowStatus::Stashed, _) => "still stashed".to_owned(),
+                (WindowStatus::InPlace, _) => "still in place".to_owned(),
+                (WindowStatus::Moved, Some(workspace)) => {
+                    format!("moved to workspace {}", workspace)
+                }
+                (WindowStatus::Moved, None) => "moved".to_owned(),
+                (WindowStatus::Closed, _) => "closed".to_owned(),
+            };
+            println!(
+                "\t{} {}: {}",
+                window.address,
+                describe_client(&window.class, &window.title),
+                status
+            );
+        }
+
+        let changed = self
+            .workspaces
+            .iter()
+            .filter(|workspace| !workspace.others.is_empty())
+            .collect::<Vec<_>>();
+        if !changed.is_empty() {
+            println!("Workspaces now holding other windows:");
+            for workspace in changed {
+                println!("\t{}: {}", workspace.workspace, workspace.others.join(", "));
+            }
+        }
+
+        if !self.monitors.is_empty() {
+            println!("Monitors:");
+        }
+        for monitor in self.monitors.iter() {
+            let stashed = match &monitor.name {
+                Some(name) => format!("{} ({})", monitor.monitor, name),
+                None => monitor.monitor.to_string(),
+            };
+            match monitor.target {
+                Some(target) => println!(
+                    "\t{} -> {}{}",
+                    stashed,
+                    target,
+                    if monitor.relative { ", relative" } else { "" }
+                ),
+                None if monitor.relative => println!(
+                    "\t{} is missing, its workspaces would be popped relative to monitor {}",
+                    stashed, monitor.monitor
+                ),
+                None => println!("\t{} is missing", stashed),
+            }
+        }
+    }
+}
@@ -1,19 +1,72 @@
-use hyprland::{error::HyprError, shared::MonitorId};
+use hyprland::{
+    error::HyprError,
+    shared::{MonitorId, WorkspaceId},
+};
 use std::io;
+
+use crate::output::ExitKind;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum StashError {
-    #[error("Bad name, only alphanumeric characters accepted")]
+    #[error(
+        "Bad name, only alphanumeric characters, dashes, underscores, dots, spaces and `/` separated namespaces accepted"
+    )]
     BadName,
+    #[error("No stash named {0}")]
+    StashNotFound(String),
     #[error("Got mismatched pop type")]
     MismatchedPopType,
     #[error("Unexpected missing active monitor and workspace")]
     NoActiveMonitorWorkspace,
+    #[error("Could not find a running Hyprland instance")]
+    NoHyprlandInstance,
+    #[error("Unexpected batch response from Hyprland: {0}")]
+    BadBatchResponse(String),
+    #[error("Hyprland IPC: {0}")]
+    Ipc(io::Error),
+    #[error("Hyprland state kept changing while taking a snapshot")]
+    InconsistentSnapshot,
     #[error("A session has already been stashed under the name {0}")]
     AlreadyStashed(String),
+    #[error(
+        "Stash location {0} holds windows that aren't stashed, use --collision auto to pick another"
+    )]
+    StashLocationInUse(WorkspaceId),
+    #[error("Stash location {0} is shown on a monitor, use --collision auto to pick another")]
+    StashLocationVisible(WorkspaceId),
+    #[error("Workspace {0} is a stash location and can't be stashed itself")]
+    StashingStashLocation(WorkspaceId),
+    #[error("Control request is missing a stash name")]
+    MissingRequestName,
+    #[error("Daemon: {0}")]
+    Daemon(String, ExitKind),
+    #[error("A daemon is already running for this Hyprland instance")]
+    DaemonRunning,
+    #[error("Only monitor and session stashes can be popped in part")]
+    BadSelection,
+    #[error("No stashed workspace matches --only-workspace and --only-monitor")]
+    EmptySelection,
+    #[error("There are no pushed stashes")]
+    EmptyStack,
+    #[error("{} stashes could not be popped:\n\t{}", .0.len(), .0.join("\n\t"))]
+    PopAllFailed(Vec<String>),
+    #[error("Workspace {0} is occupied, pick another --conflict policy to pop anyway")]
+    WorkspaceOccupied(WorkspaceId),
     #[error("Monitor {0} not found")]
     MonitorNotFound(MonitorId),
+    #[error("Launcher command is empty")]
+    EmptyLauncher,
+    #[error("Unknown stash scope {0}")]
+    BadScope(String),
+    #[error("Unexpected missing home dir")]
+    NoHomeDir,
+    #[error("Bad duration: {0}")]
+    BadDuration(String),
+    #[error("Hook {0} has an empty command")]
+    EmptyHook(String),
+    #[error("Hook {0} failed, aborting")]
+    HookFailed(String),
     #[error("Multiple dispatch errors")]
     Dispatch(DispatchError),
     #[error("IO {0}")]
@@ -32,12 +85,11 @@ impl DispatchError {
         (!self.0.is_empty()).then_some(self)
     }
 
-    pub fn print_errors(self) {
-        if !self.0.is_empty() {
-            eprintln!("Completed operation, but with the following dispatch errors:");
-            for error in self.0 {
-                eprintln!("\t{}", error)
-            }
-        }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn messages(&self) -> Vec<String> {
+        self.0.iter().map(|error| error.to_string()).collect()
     }
 }
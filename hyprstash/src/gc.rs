@@ -0,0 +1,97 @@
+use super::*;
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GcPolicy {
+    /// Forget the stash, its windows stay on the stash workspace
+    #[default]
+    Remove,
+    /// Pop the stash back
+    Pop,
+    /// Close the stashed windows
+    Close,
+}
+
+pub fn collect_garbage(
+    data: &Data,
+    expired_policy: GcPolicy,
+    dry_run: bool,
+    config: &Config,
+) -> Result<Outcome> {
+    let mut outcome = Outcome::default();
+    let mut removed = vec![];
+    let mut expired = vec![];
+    for name in StashedInstance::list_instances()? {
+        let (instance, meta) = match StashedInstance::read_with_meta(&name) {
+            Ok(stash) => stash,
+            Err(error) => {
+                outcome
+                    .warnings
+                    .push(format!("Skipping unreadable stash {}: {}", name, error));
+                continue;
+            }
+        };
+
+        let live_clients = instance
+            .client_addresses()
+            .into_iter()
+            .filter(|address| data.client(address).is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if live_clients.is_empty() {
+            outcome
+                .warnings
+                .push(format!("Removing {}: its windows no longer exist", name));
+            removed.push(name.clone());
+            if !dry_run {
+                StashedInstance::remove_instance(&name);
+            }
+            continue;
+        }
+
+        if !meta.is_expired() {
+            continue;
+        }
+
+        // Kept stashes left their windows in use, those are neither closed nor moved.
+        let policy = if meta.keep {
+            GcPolicy::Remove
+        } else {
+            expired_policy
+        };
+        outcome
+            .warnings
+            .push(format!("Stash {} expired, applying {:?}", name, policy));
+        expired.push(name.clone());
+        if dry_run {
+            continue;
+        }
+        match policy {
+            GcPolicy::Remove => StashedInstance::remove_instance(&name),
+            GcPolicy::Pop => {
+                let options = PopOptions {
+                    force: true,
+                    ..PopOptions::from_policy(&config.policy(&name))
+                };
+                // Every pop changes the compositor state, and one stash failing to pop
+                // shouldn't keep the others around.
+                match Data::new().and_then(|data| pop(&data, &name, instance, &options, config)) {
+                    Ok(report) => outcome.popped.push(report),
+                    Err(error) => outcome
+                        .errors
+                        .push(format!("Could not pop expired stash {}: {}", name, error)),
+                }
+            }
+            GcPolicy::Close => {
+                if let Some(errors) = close_clients(&live_clients) {
+                    outcome.errors.append(&mut errors.messages());
+                }
+                StashedInstance::remove_instance(&name);
+            }
+        }
+    }
+
+    outcome.result = Some(serde_json::json!({ "removed": removed, "expired": expired }));
+    Ok(outcome)
+}
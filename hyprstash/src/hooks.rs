@@ -0,0 +1,94 @@
+use super::*;
+
+use std::{
+    io,
+    os::fd::AsFd,
+    process::{Command, Stdio},
+};
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct Hooks {
+    pub pre_stash: Option<Vec<String>>,
+    pub on_stash: Option<Vec<String>>,
+    pub pre_pop: Option<Vec<String>>,
+    pub on_pop: Option<Vec<String>>,
+    pub on_pop_missing_monitor: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Hook {
+    PreStash,
+    OnStash,
+    PrePop,
+    OnPop,
+    OnPopMissingMonitor,
+}
+
+impl Hook {
+    fn name(self) -> &'static str {
+        match self {
+            Hook::PreStash => "pre_stash",
+            Hook::OnStash => "on_stash",
+            Hook::PrePop => "pre_pop",
+            Hook::OnPop => "on_pop",
+            Hook::OnPopMissingMonitor => "on_pop_missing_monitor",
+        }
+    }
+
+    // Hooks that run before anything is moved get to abort the operation.
+    fn can_abort(self) -> bool {
+        matches!(
+            self,
+            Hook::PreStash | Hook::PrePop | Hook::OnPopMissingMonitor
+        )
+    }
+}
+
+pub struct HookContext<'a> {
+    pub name: &'a str,
+    pub stash_type: &'a str,
+    pub description: String,
+    pub extra_env: Vec<(&'static str, String)>,
+}
+
+impl Hooks {
+    fn command(&self, hook: Hook) -> Option<&Vec<String>> {
+        match hook {
+            Hook::PreStash => self.pre_stash.as_ref(),
+            Hook::OnStash => self.on_stash.as_ref(),
+            Hook::PrePop => self.pre_pop.as_ref(),
+            Hook::OnPop => self.on_pop.as_ref(),
+            Hook::OnPopMissingMonitor => self.on_pop_missing_monitor.as_ref(),
+        }
+    }
+
+    pub fn run(&self, hook: Hook, context: &HookContext) -> Result<()> {
+        let Some(command) = self.command(hook) else {
+            return Ok(());
+        };
+        let Some((program, args)) = command.split_first() else {
+            return Err(StashError::EmptyHook(hook.name().to_owned()).into());
+        };
+
+        // Stdout belongs to `--output json` and `push`, hooks print next to our messages instead.
+        let status = Command::new(program)
+            .args(args)
+            .stdout(Stdio::from(io::stderr().as_fd().try_clone_to_owned()?))
+            .env("HYPRSTASH_HOOK", hook.name())
+            .env("HYPRSTASH_NAME", context.name)
+            .env("HYPRSTASH_TYPE", context.stash_type)
+            .env("HYPRSTASH_DESCRIPTION", &context.description)
+            .envs(context.extra_env.iter().map(|(k, v)| (k, v)))
+            .status();
+
+        let failed = !matches!(status, Ok(status) if status.success());
+        if failed && hook.can_abort() {
+            return Err(StashError::HookFailed(hook.name().to_owned()).into());
+        } else if failed {
+            eprintln!("Hook {} failed", hook.name());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use super::*;
+
+use std::{
+    env,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+};
+
+// Hyprland's per-instance directory, which also holds the hyprstash control socket.
+pub fn hypr_instance_dir() -> Result<PathBuf> {
+    let runtime_dir = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime_dir) => PathBuf::from(runtime_dir),
+        None => {
+            let uid = env::var("UID").map_err(|_| StashError::NoHyprlandInstance)?;
+            PathBuf::from(format!("/run/user/{}", uid))
+        }
+    };
+    let signature =
+        env::var("HYPRLAND_INSTANCE_SIGNATURE").map_err(|_| StashError::NoHyprlandInstance)?;
+
+    Ok(runtime_dir.join("hypr").join(signature))
+}
+
+// The hyprland crate only exposes one request per connection, batching lets
+// Hyprland answer everything from the same state.
+pub fn batch_request(commands: &[&str]) -> Result<Vec<serde_json::Value>> {
+    timed("batch", commands.join(";"), || send_batch(commands))
+}
+
+fn send_batch(commands: &[&str]) -> Result<Vec<serde_json::Value>> {
+    let mut stream =
+        UnixStream::connect(hypr_instance_dir()?.join(".socket.sock")).map_err(StashError::Ipc)?;
+    stream
+        .write_all(format!("[[BATCH]]{}", commands.join(";")).as_bytes())
+        .map_err(StashError::Ipc)?;
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .map_err(StashError::Ipc)?;
+
+    let values = serde_json::Deserializer::from_str(&response)
+        .into_iter::<serde_json::Value>()
+        .collect::<Result<Vec<_>, _>>()?;
+    if values.len() != commands.len() {
+        return Err(StashError::BadBatchResponse(response).into());
+    }
+
+    Ok(values)
+}
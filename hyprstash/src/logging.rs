@@ -0,0 +1,103 @@
+use super::*;
+
+use std::{
+    fmt::Display,
+    fs::{self, OpenOptions},
+    sync::Mutex,
+    time::Instant,
+};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{EnvFilter, Layer, fmt, layer::SubscriberExt, util::SubscriberInitExt};
+
+// Takes `tracing` filter directives, e.g. `debug` or `hyprstash::stashes=trace`.
+const LOG_ENV: &str = "HYPRSTASH_LOG";
+const STATE_DIR_NAME: &str = "hyprstash";
+const LOG_FILE_NAME: &str = "hyprstash.log";
+
+// `$XDG_STATE_HOME/hyprstash`, falling back to `$HOME/.local/state/hyprstash`.
+pub fn state_dir() -> Result<PathBuf> {
+    let state_home = match env::var_os("XDG_STATE_HOME") {
+        Some(state_home) if !state_home.is_empty() => PathBuf::from(state_home),
+        _ => env::home_dir()
+            .ok_or(StashError::NoHomeDir)?
+            .join(".local")
+            .join("state"),
+    };
+    Ok(state_home.join(STATE_DIR_NAME))
+}
+
+// Debug logs snapshots and plan decisions, trace also every dispatch and every client a
+// plan looked at. Flags win over the environment.
+fn filter(verbose: u8) -> EnvFilter {
+    match verbose {
+        0 => {
+            let directives = env::var(LOG_ENV).unwrap_or_default();
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::OFF.into())
+                .parse(&directives)
+                .unwrap_or_else(|error| {
+                    eprintln!("Ignoring {} '{}': {}", LOG_ENV, directives, error);
+                    EnvFilter::new("off")
+                })
+        }
+        1 => EnvFilter::new("hyprstash=debug"),
+        _ => EnvFilter::new("hyprstash=trace"),
+    }
+}
+
+// A log file on its own logs at debug level, so that keybind-triggered runs leave
+// something behind without anything printed.
+pub fn init_logging(verbose: u8, log_file: bool) -> Result<()> {
+    let file = if log_file {
+        let dir = state_dir()?;
+        fs::create_dir_all(&dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(LOG_FILE_NAME))?;
+        let file_filter = match filter(verbose) {
+            filter if filter.max_level_hint() >= Some(LevelFilter::DEBUG) => filter,
+            _ => EnvFilter::new("hyprstash=debug"),
+        };
+        Some(
+            fmt::layer()
+                .with_writer(Mutex::new(file))
+                .with_ansi(false)
+                .with_filter(file_filter),
+        )
+    } else {
+        None
+    };
+
+    let _ = tracing_subscriber::registry()
+        .with(
+            fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_ansi(false)
+                .with_filter(filter(verbose)),
+        )
+        .with(file)
+        .try_init();
+    Ok(())
+}
+
+// Runs a request to the compositor, tracing it with its timing and outcome.
+pub fn timed<T, E: Display>(
+    event: &str,
+    request: String,
+    f: impl FnOnce() -> std::result::Result<T, E>,
+) -> std::result::Result<T, E> {
+    let start = Instant::now();
+    let res = f();
+    trace!(
+        request,
+        elapsed_us = start.elapsed().as_micros(),
+        result = match &res {
+            Ok(_) => "ok".to_owned(),
+            Err(error) => error.to_string(),
+        },
+        "{}",
+        event,
+    );
+    res
+}
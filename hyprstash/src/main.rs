@@ -1,24 +1,71 @@
 use anyhow::Result;
-use clap::{ArgAction, Parser, Subcommand};
+use clap::{ArgAction, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::{ArgValueCompleter, CompleteEnv};
 use hyprland::{
     dispatch::*,
-    prelude::*,
     shared::{Address, MonitorId, WorkspaceId},
 };
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    path::PathBuf,
+    process::ExitCode,
+    time::Duration,
+};
+use tracing::{debug, trace};
 
+mod actions;
+mod completions;
+mod config;
+mod control;
+mod daemon;
 mod data;
+mod diff;
 mod error;
+mod gc;
+mod hooks;
+mod ipc;
+mod logging;
+mod menu;
+mod notify;
+mod output;
+mod registry;
+mod relaunch;
+mod report;
+mod search;
+mod stack;
 mod stashes;
 mod state;
+mod status;
 mod util;
+mod window;
 
+use actions::*;
+use completions::*;
+use config::*;
+use control::*;
+use daemon::*;
 use data::*;
+use diff::*;
 use error::*;
+use gc::*;
+use hooks::*;
+use ipc::*;
+use logging::*;
+use menu::*;
+use notify::*;
+use output::*;
+use registry::*;
+use relaunch::*;
+use report::*;
+use search::*;
+use stack::*;
 use stashes::*;
 use state::*;
+use status::*;
 use util::*;
+use window::*;
 
 const DEFAULT_STASH_LOCATION: WorkspaceId = 8;
 
@@ -26,8 +73,30 @@ const DEFAULT_STASH_LOCATION: WorkspaceId = 8;
 #[command(version)]
 struct Cli {
     #[arg(long)]
+    config: Option<String>,
+
+    #[arg(long, add = ArgValueCompleter::new(complete_workspaces))]
     stash_location: Option<WorkspaceId>,
 
+    /// Where to report results and errors, overrides the config
+    #[arg(long, value_enum)]
+    notify: Option<NotifySink>,
+
+    /// Talk to this Hyprland instance instead of $HYPRLAND_INSTANCE_SIGNATURE
+    #[arg(long)]
+    instance: Option<String>,
+
+    #[arg(long, value_enum, default_value_t)]
+    output: OutputFormat,
+
+    /// Log snapshots and plan decisions to stderr, -vv also logs every dispatch
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Also append the log to $XDG_STATE_HOME/hyprstash/hyprstash.log
+    #[arg(long, action = ArgAction::SetTrue)]
+    log_file: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -36,131 +105,927 @@ struct Cli {
 enum Commands {
     StashWorkspace {
         name: String,
-        #[arg(long)]
+        #[arg(long, add = ArgValueCompleter::new(complete_workspaces))]
         workspace: Option<WorkspaceId>,
+
+        #[command(flatten)]
+        args: StashArgs,
     },
     StashMonitor {
         name: String,
-        #[arg(long)]
+        #[arg(long, add = ArgValueCompleter::new(complete_monitor_ids))]
         monitor: Option<MonitorId>,
+
+        #[command(flatten)]
+        args: StashArgs,
     },
     StashEverything {
         name: String,
+
+        #[command(flatten)]
+        args: StashArgs,
     },
-    List,
-    // TODO: Implement Generic Pop
-    PopWorkspace {
+    /// Stash under a generated name on top of the stack, printing the name
+    Push {
+        #[arg(long, value_enum, default_value_t)]
+        scope: StashScope,
+
+        #[command(flatten)]
+        args: StashArgs,
+    },
+    List {
+        /// Only list stashes carrying all of these tags
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+    },
+    /// Search stash names, tags, notes and stashed window classes and titles
+    Find { query: String },
+    /// Print a stash with its metadata as JSON
+    Show {
+        #[arg(add = ArgValueCompleter::new(complete_names))]
         name: String,
+    },
+    /// Compare a stash to the current desktop, previewing what popping it would do
+    Diff {
+        #[arg(add = ArgValueCompleter::new(complete_names))]
+        name: String,
+
+        #[command(flatten)]
+        mode: ModeArgs,
+
+        #[command(flatten)]
+        monitors: MonitorArgs,
+    },
+    /// Serve stash, pop, list, show and subscribe requests on a control socket
+    Daemon,
+    /// Print a completion script for the given shell
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    Status {
+        #[arg(long, value_enum, default_value_t)]
+        format: StatusFormat,
+
+        /// Re-emit whenever the stash store or the window set changes
+        #[arg(long, action = ArgAction::SetTrue)]
+        follow: bool,
+    },
+    Config {
+        /// Print the config after merging defaults, per-stash policy and flags
+        #[arg(long, action = ArgAction::SetTrue)]
+        print_effective: bool,
+
+        /// Also print the merged policy for this stash
+        #[arg(long, add = ArgValueCompleter::new(complete_names))]
+        stash: Option<String>,
+    },
+    /// Pop a stash, or the top of the stack when no name is given
+    Pop {
+        #[arg(add = ArgValueCompleter::new(complete_names))]
+        name: Option<String>,
+
+        #[command(flatten)]
+        mode: ModeArgs,
+
+        #[command(flatten)]
+        monitors: MonitorArgs,
+
+        #[command(flatten)]
+        here: HereArgs,
+
+        #[command(flatten)]
+        reconcile: ReconcileArgs,
+
+        #[command(flatten)]
+        only: OnlyArgs,
+
+        /// Pop a stash made in another Hyprland instance by relaunching its programs
         #[arg(long)]
+        from_instance: Option<String>,
+    },
+    Menu {
+        /// Prompt for a name and scope to stash instead of picking a stash to pop
+        #[arg(long, action = ArgAction::SetTrue)]
+        stash: bool,
+
+        /// Any dmenu-compatible command
+        #[arg(long, default_value = DEFAULT_LAUNCHER)]
+        launcher: String,
+
+        #[command(flatten)]
+        here: HereArgs,
+    },
+    PopWorkspace {
+        #[arg(add = ArgValueCompleter::new(complete_names))]
+        name: String,
+        #[arg(long, add = ArgValueCompleter::new(complete_workspaces))]
         target: Option<WorkspaceId>,
+
+        #[command(flatten)]
+        here: HereArgs,
+
+        #[command(flatten)]
+        reconcile: ReconcileArgs,
     },
     PopMonitor {
+        #[arg(add = ArgValueCompleter::new(complete_names))]
         name: String,
-        #[arg(long)]
+        #[arg(long, add = ArgValueCompleter::new(complete_monitor_ids))]
         target: Option<MonitorId>,
 
-        #[arg(long, action = ArgAction::SetTrue)]
-        relative: bool,
+        #[command(flatten)]
+        mode: ModeArgs,
+
+        #[arg(long, value_enum)]
+        monitor_match: Option<MonitorMatch>,
+
+        #[command(flatten)]
+        here: HereArgs,
+
+        #[command(flatten)]
+        reconcile: ReconcileArgs,
+
+        #[command(flatten)]
+        only: OnlyArgs,
+    },
+    /// Pop every stash, newest first
+    PopAll {
+        #[command(flatten)]
+        mode: ModeArgs,
+
+        #[command(flatten)]
+        monitors: MonitorArgs,
+
+        #[command(flatten)]
+        here: HereArgs,
+
+        #[command(flatten)]
+        reconcile: ReconcileArgs,
     },
     PopSession {
+        #[arg(add = ArgValueCompleter::new(complete_names))]
         name: String,
 
-        #[arg(long, action = ArgAction::SetTrue)]
-        relative: bool,
+        #[command(flatten)]
+        mode: ModeArgs,
 
-        #[arg(long, action = ArgAction::SetTrue)]
-        no_missing_monitors: bool,
+        #[command(flatten)]
+        monitors: MonitorArgs,
+
+        #[command(flatten)]
+        here: HereArgs,
+
+        #[command(flatten)]
+        reconcile: ReconcileArgs,
+
+        #[command(flatten)]
+        only: OnlyArgs,
     },
     Clear {
+        #[arg(add = ArgValueCompleter::new(complete_names))]
         name: Option<String>,
     },
+    Rename {
+        #[arg(add = ArgValueCompleter::new(complete_names))]
+        old: String,
+        new: String,
+    },
+    Copy {
+        #[arg(add = ArgValueCompleter::new(complete_names))]
+        source: String,
+        destination: String,
+    },
+    /// Remove stashes whose windows are gone and handle expired ones
+    Gc {
+        /// What to do with expired stashes, overrides the config
+        #[arg(long, value_enum)]
+        policy: Option<GcPolicy>,
+
+        #[arg(long, action = ArgAction::SetTrue)]
+        dry_run: bool,
+    },
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+// Flags only ever override the config, so unset flags map to `None`.
 
-    let data = Data::new()?;
+#[derive(Args)]
+struct StashArgs {
+    /// Expire the stash after a duration such as `30m`, `8h` or `2d`
+    #[arg(long, value_parser = parse_duration)]
+    expire: Option<Duration>,
 
-    match cli.command {
-        Commands::StashWorkspace { name, workspace } => {
-            StashedInstance::check_already_stashed(&name)?;
+    /// Tag the stash, can be given multiple times
+    #[arg(long = "tag")]
+    tags: Vec<String>,
 
-            let (instance, dispatch_error) = workspace_stash(
-                &data,
-                workspace.unwrap_or(data.active_workspace),
-                cli.stash_location.unwrap_or(DEFAULT_STASH_LOCATION),
-            )?;
-            StashedInstance::Workspace(instance).write(&name)?;
+    /// Describe the stash
+    #[arg(long)]
+    note: Option<String>,
 
-            if let Some(errors) = dispatch_error {
-                errors.print_errors();
-            }
+    /// What to do when the stash location holds windows that aren't stashed
+    #[arg(long, value_enum)]
+    collision: Option<CollisionPolicy>,
+
+    /// Only record the layout, leaving every window where it is
+    #[arg(long, action = ArgAction::SetTrue)]
+    keep: bool,
+}
+
+impl StashArgs {
+    fn policy(&self) -> Policy {
+        Policy {
+            collision: self.collision,
+            keep: self.keep.then_some(true),
+            expire: self.expire.map(|expire| format!("{}s", expire.as_secs())),
+            ..Default::default()
         }
-        Commands::StashMonitor { name, monitor } => {
-            StashedInstance::check_already_stashed(&name)?;
+    }
 
-            let (instance, dispatch_error) = monitor_stash(
-                &data,
-                monitor.unwrap_or(data.active_monitor),
-                cli.stash_location.unwrap_or(DEFAULT_STASH_LOCATION),
-            )?;
-            StashedInstance::Monitor(instance).write(&name)?;
+    fn meta(&self, policy: &Policy) -> Result<StashMeta> {
+        Ok(StashMeta {
+            tags: self.tags.clone(),
+            note: self.note.clone(),
+            ..StashMeta::new(policy.expire()?)?
+        })
+    }
 
-            if let Some(errors) = dispatch_error {
-                errors.print_errors();
+    // Pushes are named by whoever does the stashing.
+    fn request(&self, name: Option<&str>, scope: StashScope, cli_policy: &Policy) -> Request {
+        Request {
+            method: if name.is_some() {
+                Method::Stash
+            } else {
+                Method::Push
+            },
+            name: name.map(str::to_owned),
+            scope: Some(scope),
+            policy: cli_policy.merge(&self.policy()),
+            tags: self.tags.clone(),
+            note: self.note.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Args)]
+struct ModeArgs {
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with = "absolute")]
+    relative: bool,
+
+    /// Override a relative pop mode from the config
+    #[arg(long, action = ArgAction::SetTrue)]
+    absolute: bool,
+
+    /// Bring back the original workspace ids, moving them over from other monitors
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["relative", "absolute"])]
+    exact: bool,
+
+    /// Move the windows back to their recorded workspaces and monitors from wherever they are
+    #[arg(long, action = ArgAction::SetTrue, conflicts_with_all = ["relative", "absolute", "exact"])]
+    restore: bool,
+}
+
+impl ModeArgs {
+    fn policy(&self) -> Policy {
+        let pop_mode = if self.relative {
+            Some(PopMode::Relative)
+        } else if self.absolute {
+            Some(PopMode::Absolute)
+        } else if self.exact {
+            Some(PopMode::Exact)
+        } else if self.restore {
+            Some(PopMode::Restore)
+        } else {
+            None
+        };
+        Policy {
+            pop_mode,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Args)]
+struct MonitorArgs {
+    #[arg(long, action = ArgAction::SetTrue)]
+    no_missing_monitors: bool,
+
+    /// How stashed monitors are matched against connected ones
+    #[arg(long, value_enum)]
+    monitor_match: Option<MonitorMatch>,
+}
+
+impl MonitorArgs {
+    fn policy(&self) -> Policy {
+        Policy {
+            no_missing_monitors: self.no_missing_monitors.then_some(true),
+            monitor_match: self.monitor_match,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Args)]
+struct HereArgs {
+    /// Pop onto the focused workspace or monitor instead of the original one
+    #[arg(long, action = ArgAction::SetTrue)]
+    here: bool,
+}
+
+impl HereArgs {
+    fn policy(&self) -> Policy {
+        Policy {
+            here: self.here.then_some(true),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Args)]
+struct OnlyArgs {
+    /// Only pop this stashed workspace of a monitor or session stash, can be given multiple times
+    #[arg(long = "only-workspace", add = ArgValueCompleter::new(complete_workspaces))]
+    workspaces: Vec<WorkspaceId>,
+
+    /// Only pop the stashed monitor with this description, name or id, can be given multiple times
+    #[arg(long = "only-monitor", add = ArgValueCompleter::new(complete_monitor_names))]
+    monitors: Vec<String>,
+}
+
+impl OnlyArgs {
+    fn selection(&self) -> PopSelection {
+        PopSelection {
+            workspaces: self.workspaces.clone(),
+            monitors: self.monitors.clone(),
+        }
+    }
+}
+
+#[derive(Args)]
+struct ReconcileArgs {
+    /// Pull stashed windows back even if they were moved out of the stash workspace
+    #[arg(long, action = ArgAction::SetTrue)]
+    steal: bool,
+
+    /// Remove the stash even if some windows could not be restored
+    #[arg(long, action = ArgAction::SetTrue)]
+    force: bool,
+
+    /// What to do when a workspace to pop onto has windows of its own
+    #[arg(long, value_enum)]
+    conflict: Option<ConflictPolicy>,
+
+    /// Window properties to leave alone instead of restoring them
+    #[arg(long, value_enum, value_delimiter = ',')]
+    no_restore: Vec<WindowProperty>,
+}
+
+impl ReconcileArgs {
+    fn policy(&self) -> Policy {
+        Policy {
+            steal: self.steal.then_some(true),
+            force: self.force.then_some(true),
+            conflict: self.conflict,
+            no_restore: (!self.no_restore.is_empty()).then(|| self.no_restore.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    // Answers the shell and exits when it asks for completions.
+    CompleteEnv::with_factory(Cli::command)
+        .var(COMPLETE_ENV)
+        .complete();
+
+    let cli = Cli::parse();
+    let output = cli.output;
+
+    let res = try_main(cli);
+    let status = match &res {
+        Ok(outcome) => outcome.exit_kind(),
+        Err(error) => ExitKind::from_error(error),
+    };
+    match output {
+        OutputFormat::Text => match &res {
+            Ok(outcome) => outcome.print(),
+            Err(error) => eprintln!("Error: {:?}", error),
+        },
+        OutputFormat::Json => {
+            if let Err(error) = print_json_output(&res) {
+                eprintln!("Error: {:?}", error);
             }
         }
-        Commands::StashEverything { name } => {
-            StashedInstance::check_already_stashed(&name)?;
+    }
+    status.into()
+}
+
+fn try_main(cli: Cli) -> Result<Outcome> {
+    if let Some(instance) = cli.instance.as_ref() {
+        // SAFETY: nothing else is running yet, the hyprland crate reads this lazily.
+        unsafe { env::set_var("HYPRLAND_INSTANCE_SIGNATURE", instance) };
+    }
+
+    let mut config = match Config::load_config(cli.config.clone()) {
+        Ok(config) => config,
+        Err(error) => {
+            cli.notify
+                .unwrap_or_default()
+                .send(Urgency::Error, &error.to_string());
+            return Err(error);
+        }
+    };
+    if let Some(notify) = cli.notify {
+        config.notify = notify;
+    }
+    init_logging(cli.verbose, cli.log_file || config.log_file)?;
 
-            let (instance, dispatch_error) =
-                everything_stash(&data, cli.stash_location.unwrap_or(DEFAULT_STASH_LOCATION))?;
-            StashedInstance::Everything(instance).write(&name)?;
+    // Stashes from another instance are read from, and removed from, its part of the store.
+    let current = env::var("HYPRLAND_INSTANCE_SIGNATURE").ok();
+    let signature = match &cli.command {
+        Commands::Pop {
+            from_instance: Some(instance),
+            ..
+        } => Some(instance.clone()),
+        _ => current.clone(),
+    };
+    let base = config.store_path.as_ref().map(PathBuf::from);
+    StashedInstance::set_store_path(base.clone(), signature.as_deref());
+    if current.is_some()
+        && signature == current
+        && !matches!(cli.command, Commands::Completions { .. })
+    {
+        StashedInstance::adopt_unpartitioned(base);
+    }
 
-            if let Some(errors) = dispatch_error {
-                errors.print_errors();
+    let res = run(cli, &config);
+    if let Err(error) = &res {
+        config.notify.send(Urgency::Error, &error.to_string());
+    }
+    res
+}
+
+// Only commands the daemon can do on our behalf, with flags but not the config applied.
+fn control_request(command: &Commands, cli_policy: &Policy) -> Option<Request> {
+    let pop = |name: &Option<String>, scope: Option<StashScope>, policy: Policy| Request {
+        method: Method::Pop,
+        name: name.clone(),
+        scope,
+        policy: cli_policy.merge(&policy),
+        ..Default::default()
+    };
+    match command {
+        Commands::StashWorkspace {
+            name,
+            workspace,
+            args,
+        } => Some(Request {
+            workspace: *workspace,
+            ..args.request(Some(name), StashScope::Workspace, cli_policy)
+        }),
+        Commands::StashMonitor {
+            name,
+            monitor,
+            args,
+        } => Some(Request {
+            monitor: *monitor,
+            ..args.request(Some(name), StashScope::Monitor, cli_policy)
+        }),
+        Commands::StashEverything { name, args } => {
+            Some(args.request(Some(name), StashScope::Everything, cli_policy))
+        }
+        Commands::Push { scope, args } => Some(args.request(None, *scope, cli_policy)),
+        Commands::Pop {
+            name,
+            mode,
+            monitors,
+            here,
+            reconcile,
+            only,
+            from_instance: None,
+        } => Some(Request {
+            only: only.selection(),
+            ..pop(
+                name,
+                None,
+                mode.policy()
+                    .merge(&monitors.policy())
+                    .merge(&here.policy())
+                    .merge(&reconcile.policy()),
+            )
+        }),
+        Commands::PopWorkspace {
+            name,
+            target,
+            here,
+            reconcile,
+        } => Some(Request {
+            workspace: *target,
+            ..pop(
+                &Some(name.clone()),
+                Some(StashScope::Workspace),
+                here.policy().merge(&reconcile.policy()),
+            )
+        }),
+        Commands::PopMonitor {
+            name,
+            target,
+            mode,
+            monitor_match,
+            here,
+            reconcile,
+            only,
+        } => Some(Request {
+            monitor: *target,
+            only: only.selection(),
+            ..pop(
+                &Some(name.clone()),
+                Some(StashScope::Monitor),
+                mode.policy()
+                    .merge(&Policy {
+                        monitor_match: *monitor_match,
+                        ..Default::default()
+                    })
+                    .merge(&here.policy())
+                    .merge(&reconcile.policy()),
+            )
+        }),
+        Commands::PopSession {
+            name,
+            mode,
+            monitors,
+            here,
+            reconcile,
+            only,
+        } => Some(Request {
+            only: only.selection(),
+            ..pop(
+                &Some(name.clone()),
+                Some(StashScope::Everything),
+                mode.policy()
+                    .merge(&monitors.policy())
+                    .merge(&here.policy())
+                    .merge(&reconcile.policy()),
+            )
+        }),
+        Commands::PopAll {
+            mode,
+            monitors,
+            here,
+            reconcile,
+        } => Some(Request {
+            method: Method::PopAll,
+            policy: cli_policy
+                .merge(&mode.policy())
+                .merge(&monitors.policy())
+                .merge(&here.policy())
+                .merge(&reconcile.policy()),
+            ..Default::default()
+        }),
+        Commands::Gc { policy, dry_run } => Some(Request {
+            method: Method::Gc,
+            gc: *policy,
+            dry_run: *dry_run,
+            ..Default::default()
+        }),
+        _ => None,
+    }
+}
+
+fn run(cli: Cli, config: &Config) -> Result<Outcome> {
+    let json = cli.output == OutputFormat::Json;
+    let cli_policy = Policy {
+        stash_location: cli.stash_location,
+        ..Default::default()
+    };
+    let policy = |name: &str, flags: Policy| config.policy(name).merge(&cli_policy).merge(&flags);
+
+    // These do not touch the compositor state, so don't require it.
+    match cli.command {
+        Commands::Status { format, follow } => {
+            if follow {
+                follow_status(format)?;
+            } else if json {
+                return Outcome::result(status_entries()?);
+            } else {
+                print_status(format)?;
+            }
+            return Ok(Outcome::default());
+        }
+        Commands::Config {
+            print_effective,
+            stash,
+        } => {
+            if !print_effective {
+                let (path, _) = Config::resolve_config_path(cli.config)?;
+                if json {
+                    return Outcome::result(path);
+                }
+                println!("{}", path.display());
+                return Ok(Outcome::default());
+            }
+
+            let mut effective = config.clone();
+            effective.defaults = effective.defaults.merge(&cli_policy);
+            effective.store_path = Some(StashedInstance::store_path().display().to_string());
+            let stash = stash.map(|name| policy(&name, Policy::default()));
+            if json {
+                return Outcome::result(serde_json::json!({
+                    "config": effective,
+                    "stash": stash,
+                }));
+            }
+            println!("{}", serde_json::to_string_pretty(&effective)?);
+            if let Some(stash) = stash {
+                println!("{}", serde_json::to_string_pretty(&stash)?);
             }
+            return Ok(Outcome::default());
         }
-        Commands::List => {
-            for entry in StashedInstance::list_instances()? {
+        Commands::List { tags } => {
+            let entries = list_by_tags(&tags)?;
+            if json {
+                return Outcome::result(entries);
+            }
+            for entry in entries {
                 println!("{}", entry);
             }
+            return Ok(Outcome::default());
+        }
+        Commands::Rename { old, new } => {
+            StashedInstance::rename_instance(&old, &new)?;
+            return Ok(Outcome::default());
+        }
+        Commands::Copy {
+            source,
+            destination,
+        } => {
+            StashedInstance::copy_instance(&source, &destination)?;
+            return Ok(Outcome::default());
+        }
+        Commands::Show { name } => {
+            let shown = StashedInstance::read_as_json(&name)?;
+            if json {
+                return Outcome::result(shown);
+            }
+            println!("{}", serde_json::to_string_pretty(&shown)?);
+            return Ok(Outcome::default());
         }
-        Commands::PopWorkspace { name, target } => {
+        Commands::Daemon => {
+            run_daemon(config.clone())?;
+            return Ok(Outcome::default());
+        }
+        Commands::Completions { shell } => {
+            print_completions(shell, Cli::command().get_name())?;
+            return Ok(Outcome::default());
+        }
+        Commands::Find { query } => {
+            let found = find_stashes(&query)?;
+            if json {
+                return Outcome::result(found);
+            }
+            for found in found {
+                println!("{}", found.name);
+                for matched in found.matched {
+                    println!("\t{}", matched);
+                }
+            }
+            return Ok(Outcome::default());
+        }
+        _ => {}
+    }
+
+    // Let a running daemon do the work, it already has the compositor state at hand. The
+    // daemon uses its own config, so runs asking for another one do the work themselves.
+    if cli.config.is_none()
+        && cli.notify.is_none()
+        && let Some(request) = control_request(&cli.command, &cli_policy)
+        && let Some(result) = daemon_request(&request)?
+    {
+        return Ok(match request.method {
+            Method::Stash | Method::Push => {
+                let report = serde_json::from_value::<StashReport>(result)?;
+                if request.method == Method::Push && !json {
+                    println!("{}", report.name);
+                }
+                Outcome::stashed(report)
+            }
+            Method::Pop => Outcome::popped(serde_json::from_value(result)?),
+            Method::PopAll | Method::Gc => serde_json::from_value(result)?,
+            _ => Outcome {
+                result: Some(result),
+                ..Default::default()
+            },
+        });
+    }
+
+    // Every pop takes its own snapshot.
+    if let Commands::PopAll {
+        mode,
+        monitors,
+        here,
+        reconcile,
+    } = &cli.command
+    {
+        let flags = cli_policy
+            .merge(&mode.policy())
+            .merge(&monitors.policy())
+            .merge(&here.policy())
+            .merge(&reconcile.policy());
+        return pop_all(&flags, config);
+    }
+
+    let data = Data::new()?;
+
+    let outcome = match cli.command {
+        Commands::StashWorkspace {
+            name,
+            workspace,
+            args,
+        } => {
+            let policy = policy(&name, args.policy());
+            Outcome::stashed(stash(
+                &data,
+                &name,
+                StashTarget::Workspace(workspace.unwrap_or(data.active_workspace)),
+                &policy,
+                args.meta(&policy)?,
+                config,
+            )?)
+        }
+        Commands::StashMonitor {
+            name,
+            monitor,
+            args,
+        } => {
+            let policy = policy(&name, args.policy());
+            Outcome::stashed(stash(
+                &data,
+                &name,
+                StashTarget::Monitor(monitor.unwrap_or(data.active_monitor)),
+                &policy,
+                args.meta(&policy)?,
+                config,
+            )?)
+        }
+        Commands::StashEverything { name, args } => {
+            let policy = policy(&name, args.policy());
+            Outcome::stashed(stash(
+                &data,
+                &name,
+                StashTarget::Everything,
+                &policy,
+                args.meta(&policy)?,
+                config,
+            )?)
+        }
+        Commands::Push { scope, args } => {
+            let name = next_stack_name()?;
+            let policy = policy(&name, args.policy());
+            let report = stash(
+                &data,
+                &name,
+                StashTarget::active(&data, scope),
+                &policy,
+                args.meta(&policy)?,
+                config,
+            )?;
+            if !json {
+                println!("{}", name);
+            }
+            Outcome::stashed(report)
+        }
+        Commands::Status { .. }
+        | Commands::Config { .. }
+        | Commands::List { .. }
+        | Commands::Find { .. }
+        | Commands::Show { .. }
+        | Commands::Daemon
+        | Commands::PopAll { .. }
+        | Commands::Completions { .. }
+        | Commands::Rename { .. }
+        | Commands::Copy { .. } => unreachable!(),
+        Commands::Pop {
+            name,
+            mode,
+            monitors,
+            here,
+            reconcile,
+            only,
+            from_instance,
+        } => {
+            let name = match name {
+                Some(name) => name,
+                None => stack_top()?,
+            };
+            let instance = StashedInstance::new_from_name(&name)?;
+            let flags = mode
+                .policy()
+                .merge(&monitors.policy())
+                .merge(&here.policy())
+                .merge(&reconcile.policy());
+            let current = env::var("HYPRLAND_INSTANCE_SIGNATURE").ok();
+            let options = PopOptions {
+                relaunch: from_instance.is_some() && from_instance != current,
+                only: only.selection(),
+                ..PopOptions::from_policy(&policy(&name, flags))
+            };
+            Outcome::popped(pop(&data, &name, instance, &options, config)?)
+        }
+        Commands::Menu {
+            stash,
+            launcher,
+            here,
+        } => {
+            if stash {
+                menu_stash(&data, &launcher, &cli_policy, config)?
+            } else {
+                let flags = cli_policy.merge(&here.policy());
+                menu_pop(&data, &launcher, &flags, config)?
+            }
+        }
+        Commands::PopWorkspace {
+            name,
+            target,
+            here,
+            reconcile,
+        } => {
             let instance = StashedInstance::new_from_name(&name)?;
-            let StashedInstance::Workspace(stashed_workspace) = instance else {
+            let StashedInstance::Workspace(_) = instance else {
                 return Err(StashError::MismatchedPopType.into());
             };
-            workspace_pop(&data, &stashed_workspace, target)?;
-            StashedInstance::remove_instance(&name);
+            let flags = here.policy().merge(&reconcile.policy());
+            let options = PopOptions {
+                workspace_target: target,
+                ..PopOptions::from_policy(&policy(&name, flags))
+            };
+            Outcome::popped(pop(&data, &name, instance, &options, config)?)
         }
         Commands::PopMonitor {
             name,
             target,
-            relative,
+            mode,
+            monitor_match,
+            here,
+            reconcile,
+            only,
         } => {
             let instance = StashedInstance::new_from_name(&name)?;
-            let StashedInstance::Monitor(stashed_monitor) = instance else {
+            let StashedInstance::Monitor(_) = instance else {
                 return Err(StashError::MismatchedPopType.into());
             };
-            if relative {
-                monitor_pop_relative(&data, &stashed_monitor, false)?;
-            } else {
-                monitor_pop_absolute(&data, &stashed_monitor, target)?;
-            }
-            StashedInstance::remove_instance(&name);
+            let flags = mode
+                .policy()
+                .merge(&Policy {
+                    monitor_match,
+                    ..Default::default()
+                })
+                .merge(&here.policy())
+                .merge(&reconcile.policy());
+            let options = PopOptions {
+                monitor_target: target,
+                only: only.selection(),
+                ..PopOptions::from_policy(&policy(&name, flags))
+            };
+            Outcome::popped(pop(&data, &name, instance, &options, config)?)
         }
         Commands::PopSession {
             name,
-            relative,
-            no_missing_monitors,
+            mode,
+            monitors,
+            here,
+            reconcile,
+            only,
         } => {
             let instance = StashedInstance::new_from_name(&name)?;
-            let StashedInstance::Everything(stashed_session) = instance else {
+            let StashedInstance::Everything(_) = instance else {
                 return Err(StashError::MismatchedPopType.into());
             };
-            everything_pop(&data, &stashed_session, no_missing_monitors, relative)?;
-            StashedInstance::remove_instance(&name);
+            let flags = mode
+                .policy()
+                .merge(&monitors.policy())
+                .merge(&here.policy())
+                .merge(&reconcile.policy());
+            let options = PopOptions {
+                only: only.selection(),
+                ..PopOptions::from_policy(&policy(&name, flags))
+            };
+            Outcome::popped(pop(&data, &name, instance, &options, config)?)
+        }
+        Commands::Diff {
+            name,
+            mode,
+            monitors,
+        } => {
+            let instance = StashedInstance::new_from_name(&name)?;
+            let flags = mode.policy().merge(&monitors.policy());
+            let diff = StashDiff::new(&data, &name, &instance, &policy(&name, flags));
+            if json {
+                Outcome::result(diff)?
+            } else {
+                diff.print();
+                Outcome::default()
+            }
         }
         Commands::Clear { name } => {
             if let Some(name) = name {
@@ -168,8 +1033,12 @@ fn main() -> Result<()> {
             } else {
                 StashedInstance::remove_all_instances();
             }
+            Outcome::default()
         }
-    }
+        Commands::Gc { policy, dry_run } => {
+            collect_garbage(&data, policy.unwrap_or(config.gc), dry_run, config)?
+        }
+    };
 
-    Ok(())
+    Ok(outcome)
 }
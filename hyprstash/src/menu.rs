@@ -0,0 +1,157 @@
+use super::*;
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+pub const DEFAULT_LAUNCHER: &str = "rofi -dmenu";
+
+// Any launcher speaking the dmenu protocol works: entries on stdin, selection on stdout.
+// It runs through the shell so that quoted arguments such as prompts survive.
+fn run_launcher(launcher: &str, entries: &[String]) -> Result<Option<String>> {
+    if launcher.trim().is_empty() {
+        return Err(StashError::EmptyLauncher.into());
+    }
+
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(launcher)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    {
+        let mut stdin = child.stdin.take().unwrap();
+        for entry in entries {
+            writeln!(stdin, "{}", entry)?;
+        }
+    }
+
+    let output = child.wait_with_output()?;
+    let selection = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+
+    // Launchers exit non-zero when the user cancels.
+    Ok((output.status.success() && !selection.is_empty()).then_some(selection))
+}
+
+fn describe_instance(data: &Data, name: &str, instance: &StashedInstance) -> String {
+    let addresses = instance.client_addresses();
+    let mut classes = addresses
+        .iter()
+        .filter_map(|address| Some(data.client(address)?.class.clone()))
+        .collect::<Vec<_>>();
+    classes.sort();
+    classes.dedup();
+
+    format!(
+        "{} — {}, {} windows ({})",
+        name,
+        instance.kind(),
+        addresses.len(),
+        classes.join(", ")
+    )
+}
+
+pub fn menu_pop(
+    data: &Data,
+    launcher: &str,
+    cli_policy: &Policy,
+    config: &Config,
+) -> Result<Outcome> {
+    let mut names = StashedInstance::list_instances()?;
+    names.sort();
+
+    let mut entries = vec![];
+    for name in names {
+        let Ok(instance) = StashedInstance::new_from_name(&name) else {
+            continue;
+        };
+        entries.push((describe_instance(data, &name, &instance), name));
+    }
+
+    let lines = entries
+        .iter()
+        .map(|(line, _)| line.clone())
+        .collect::<Vec<_>>();
+    let Some(selection) = run_launcher(launcher, &lines)? else {
+        return Ok(Outcome::default());
+    };
+
+    // Fall back to treating the selection as a typed name.
+    let name = entries
+        .into_iter()
+        .find_map(|(line, name)| (line == selection).then_some(name))
+        .unwrap_or(selection);
+
+    let instance = StashedInstance::new_from_name(&name)?;
+    let options = PopOptions::from_policy(&config.policy(&name).merge(cli_policy));
+    Ok(Outcome::popped(pop(
+        data, &name, instance, &options, config,
+    )?))
+}
+
+pub fn menu_stash(
+    data: &Data,
+    launcher: &str,
+    cli_policy: &Policy,
+    config: &Config,
+) -> Result<Outcome> {
+    let Some(name) = run_launcher(launcher, &[])? else {
+        return Ok(Outcome::default());
+    };
+
+    let scopes = StashScope::value_variants()
+        .iter()
+        .filter_map(|scope| Some(scope.to_possible_value()?.get_name().to_owned()))
+        .collect::<Vec<_>>();
+    let Some(scope) = run_launcher(launcher, &scopes)? else {
+        return Ok(Outcome::default());
+    };
+    let scope = StashScope::from_str(&scope, true).map_err(|_| StashError::BadScope(scope))?;
+
+    let policy = config.policy(&name).merge(cli_policy);
+    let report = stash(
+        data,
+        &name,
+        StashTarget::active(data, scope),
+        &policy,
+        StashMeta::new(policy.expire()?)?,
+        config,
+    )?;
+    Ok(Outcome::stashed(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|entry| entry.to_string()).collect()
+    }
+
+    #[test]
+    fn launcher_selection() {
+        let selection = run_launcher("head -n1", &entries(&["first", "second"])).unwrap();
+        assert_eq!(selection.as_deref(), Some("first"));
+    }
+
+    #[test]
+    fn launcher_quoted_arguments() {
+        let selection = run_launcher("grep -x 'b c'", &entries(&["a", "b c"])).unwrap();
+        assert_eq!(selection.as_deref(), Some("b c"));
+    }
+
+    #[test]
+    fn launcher_cancelled() {
+        let selection = run_launcher("cat >/dev/null; exit 1", &entries(&["a"])).unwrap();
+        assert_eq!(selection, None);
+        let selection = run_launcher("cat >/dev/null", &entries(&["a"])).unwrap();
+        assert_eq!(selection, None);
+    }
+
+    #[test]
+    fn launcher_empty() {
+        assert!(run_launcher(" ", &[]).is_err());
+    }
+}
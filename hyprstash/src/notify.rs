@@ -0,0 +1,63 @@
+use super::*;
+
+use hyprland::ctl::{Color, notify};
+use std::{
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+const NOTIFY_DURATION: Duration = Duration::from_secs(5);
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifySink {
+    #[default]
+    None,
+    /// Hyprland's own `notify` overlay
+    Hyprland,
+    /// Freedesktop notifications through `notify-send`
+    Desktop,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Urgency {
+    Info,
+    Warning,
+    Error,
+}
+
+impl NotifySink {
+    // Notifying is best effort, it should never fail the operation itself.
+    pub fn send(self, urgency: Urgency, message: &str) {
+        match self {
+            NotifySink::None => {}
+            NotifySink::Hyprland => {
+                let icon = match urgency {
+                    Urgency::Info => notify::Icon::Ok,
+                    Urgency::Warning => notify::Icon::Warning,
+                    Urgency::Error => notify::Icon::Error,
+                };
+                // A zero color makes Hyprland pick its default.
+                let _ = notify::call(
+                    icon,
+                    NOTIFY_DURATION,
+                    Color::new(0, 0, 0, 0),
+                    format!("hyprstash: {}", message),
+                );
+            }
+            NotifySink::Desktop => {
+                let urgency = match urgency {
+                    Urgency::Info => "low",
+                    Urgency::Warning => "normal",
+                    Urgency::Error => "critical",
+                };
+                let _ = Command::new("notify-send")
+                    .args(["--app-name", "hyprstash", "--urgency", urgency])
+                    .arg("hyprstash")
+                    .arg(message)
+                    .stdout(Stdio::null())
+                    .status();
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use super::*;
+
+use hyprland::error::HyprError;
+use std::process::ExitCode;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    /// One JSON document on stdout describing what was done
+    Json,
+}
+
+// Exit codes scripts can branch on. 2 is left to clap for usage errors.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExitKind {
+    Success,
+    Failure,
+    Partial,
+    NotFound,
+    MismatchedType,
+    MissingMonitor,
+    Ipc,
+}
+
+impl ExitKind {
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Success => 0,
+            Self::Failure => 1,
+            Self::Partial => 3,
+            Self::NotFound => 4,
+            Self::MismatchedType => 5,
+            Self::MissingMonitor => 6,
+            Self::Ipc => 7,
+        }
+    }
+
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        if error.downcast_ref::<HyprError>().is_some() {
+            return Self::Ipc;
+        }
+        match error.downcast_ref::<StashError>() {
+            Some(StashError::StashNotFound(_) | StashError::EmptyStack) => Self::NotFound,
+            Some(StashError::MismatchedPopType | StashError::BadSelection) => Self::MismatchedType,
+            Some(StashError::MonitorNotFound(_)) => Self::MissingMonitor,
+            Some(
+                StashError::NoHyprlandInstance
+                | StashError::NoActiveMonitorWorkspace
+                | StashError::BadBatchResponse(_)
+                | StashError::InconsistentSnapshot
+                | StashError::Ipc(_)
+                | StashError::Dispatch(_),
+            ) => Self::Ipc,
+            Some(StashError::Daemon(_, kind)) => *kind,
+            _ => Self::Failure,
+        }
+    }
+}
+
+impl From<ExitKind> for ExitCode {
+    fn from(kind: ExitKind) -> Self {
+        ExitCode::from(kind.code())
+    }
+}
+
+// What a command did, for `--output json` and to tell partial success apart.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Outcome {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stashed: Vec<StashReport>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub popped: Vec<PopReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl Outcome {
+    pub fn stashed(report: StashReport) -> Self {
+        Outcome {
+            stashed: vec![report],
+            ..Default::default()
+        }
+    }
+
+    pub fn popped(report: PopReport) -> Self {
+        Outcome {
+            popped: vec![report],
+            ..Default::default()
+        }
+    }
+
+    pub fn result(result: impl Serialize) -> Result<Self> {
+        Ok(Outcome {
+            result: Some(serde_json::to_value(result)?),
+            ..Default::default()
+        })
+    }
+
+    // Text output, printed the same whether the work was done here or by the daemon.
+    pub fn print(&self) {
+        for report in self.stashed.iter() {
+            print_dispatch_errors(&report.errors);
+        }
+        for report in self.popped.iter() {
+            report.print();
+        }
+        for warning in self.warnings.iter() {
+            eprintln!("{}", warning);
+        }
+        if !self.errors.is_empty() {
+            eprintln!("Completed operation, but with the following errors:");
+            for error in self.errors.iter() {
+                eprintln!("\t{}", error)
+            }
+        }
+    }
+
+    pub fn exit_kind(&self) -> ExitKind {
+        let partial = !self.errors.is_empty()
+            || self.stashed.iter().any(|report| !report.errors.is_empty())
+            || self
+                .popped
+                .iter()
+                .any(|report| report.is_partial() || !report.errors.is_empty());
+        if partial {
+            ExitKind::Partial
+        } else {
+            ExitKind::Success
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct JsonOutput<'a> {
+    ok: bool,
+    status: ExitKind,
+    code: u8,
+    #[serde(flatten)]
+    outcome: Option<&'a Outcome>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+pub fn print_json_output(res: &Result<Outcome>) -> Result<()> {
+    let status = match res {
+        Ok(outcome) => outcome.exit_kind(),
+        Err(error) => ExitKind::from_error(error),
+    };
+    let output = JsonOutput {
+        ok: res.is_ok(),
+        status,
+        code: status.code(),
+        outcome: res.as_ref().ok(),
+        error: res.as_ref().err().map(|error| error.to_string()),
+    };
+    println!("{}", serde_json::to_string(&output)?);
+    Ok(())
+}
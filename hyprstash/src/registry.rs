@@ -0,0 +1,91 @@
+use super::*;
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionPolicy {
+    /// Refuse to stash onto a workspace holding windows that aren't stashed, or one that is shown
+    #[default]
+    Refuse,
+    /// Pick the next free workspace instead
+    Auto,
+}
+
+// Which workspaces are in use as stash locations, and by which windows, as
+// recorded by the stashes in the store.
+#[derive(Debug, Default)]
+pub struct StashRegistry {
+    locations: HashMap<WorkspaceId, HashSet<Address>>,
+}
+
+impl StashRegistry {
+    pub fn load() -> Result<Self> {
+        let mut registry = StashRegistry::default();
+        for name in StashedInstance::list_instances()? {
+            let Ok(instance) = StashedInstance::new_from_name(&name) else {
+                continue;
+            };
+            for workspace in instance.stashed_workspaces() {
+                registry
+                    .locations
+                    .entry(workspace.stash_location)
+                    .or_default()
+                    .extend(workspace.client_addresses.iter().cloned());
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn locations(&self) -> HashSet<WorkspaceId> {
+        self.locations.keys().copied().collect()
+    }
+
+    fn has_unstashed_clients(&self, data: &Data, location: WorkspaceId) -> bool {
+        let stashed = self.locations.get(&location);
+        data.clients_on(location)
+            .any(|client| !stashed.is_some_and(|stashed| stashed.contains(&client.address)))
+    }
+
+    // Visible workspaces are never used, that would defeat the point of stashing.
+    fn is_visible(&self, data: &Data, location: WorkspaceId) -> bool {
+        data.monitors
+            .iter()
+            .any(|monitor| monitor.active_workspace.id == location)
+    }
+
+    fn is_free(&self, data: &Data, location: WorkspaceId) -> bool {
+        !self.has_unstashed_clients(data, location) && !self.is_visible(data, location)
+    }
+
+    pub fn resolve_location(
+        &self,
+        data: &Data,
+        desired: WorkspaceId,
+        collision: CollisionPolicy,
+    ) -> Result<WorkspaceId> {
+        if self.is_free(data, desired) {
+            return Ok(desired);
+        }
+
+        match collision {
+            CollisionPolicy::Refuse if self.has_unstashed_clients(data, desired) => {
+                Err(StashError::StashLocationInUse(desired).into())
+            }
+            CollisionPolicy::Refuse => Err(StashError::StashLocationVisible(desired).into()),
+            CollisionPolicy::Auto => {
+                let location = (desired + 1..)
+                    .find(|location| self.is_free(data, *location))
+                    .unwrap();
+                let reason = if self.has_unstashed_clients(data, desired) {
+                    "holds unstashed windows"
+                } else {
+                    "is shown on a monitor"
+                };
+                eprintln!(
+                    "Workspace {} {}, stashing onto {} instead",
+                    desired, reason, location
+                );
+                Ok(location)
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use super::*;
+
+use std::fs;
+
+// Addresses are meaningless in another Hyprland instance, so the best we can do is
+// start the stashed programs again.
+pub fn client_command(pid: i32) -> Vec<String> {
+    fs::read(format!("/proc/{}/cmdline", pid))
+        .map(|cmdline| {
+            cmdline
+                .split(|byte| *byte == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Hyprland runs `exec` through a shell.
+fn shell_quote(command: &[String]) -> String {
+    command
+        .iter()
+        .map(|arg| format!("'{}'", arg.replace('\'', r"'\''")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn relaunch_pop(
+    instance: &StashedInstance,
+    workspace_target: Option<WorkspaceId>,
+) -> Result<PopReport> {
+    let mut report = PopReport::default();
+    let mut errors = vec![];
+    for workspace in instance.stashed_workspaces() {
+        let target = workspace_target.unwrap_or(workspace.original_workspace);
+
+        for address in workspace.client_addresses.iter() {
+            let command = workspace
+                .clients
+                .iter()
+                .find(|client| &client.address == address)
+                .map(|client| client.command.as_slice())
+                .unwrap_or_default();
+            if command.is_empty() {
+                report.missing.push(address.clone());
+                continue;
+            }
+
+            let res = dispatch(DispatchType::Exec(&format!(
+                "[workspace {} silent] {}",
+                target,
+                shell_quote(command)
+            )));
+            match res {
+                Ok(()) => report.restored.push(address.clone()),
+                Err(error) => errors.push(error),
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(report)
+    } else {
+        Err(StashError::Dispatch(DispatchError(errors)).into())
+    }
+}
@@ -0,0 +1,93 @@
+use super::*;
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct StashReport {
+    pub name: String,
+    pub kind: String,
+    pub stash_location: WorkspaceId,
+    pub windows: Vec<Address>,
+    pub workspaces: Vec<WorkspaceId>,
+    // Dispatches that failed, the stash was written anyway.
+    pub errors: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct PopReport {
+    pub name: String,
+    pub kind: String,
+    pub restored: Vec<Address>,
+    pub moved: Vec<(Address, WorkspaceId)>,
+    pub missing: Vec<Address>,
+    // Stashed workspace to the workspace it was popped onto.
+    pub workspaces: Vec<(WorkspaceId, WorkspaceId)>,
+    // Window states that could not be restored.
+    pub errors: Vec<String>,
+    // What the user should know about, e.g. that a partial stash was kept.
+    pub warnings: Vec<String>,
+}
+
+impl PopReport {
+    pub fn append(&mut self, mut report: PopReport) {
+        self.restored.append(&mut report.restored);
+        self.moved.append(&mut report.moved);
+        self.missing.append(&mut report.missing);
+        self.workspaces.append(&mut report.workspaces);
+        self.errors.append(&mut report.errors);
+        self.warnings.append(&mut report.warnings);
+    }
+
+    pub fn is_partial(&self) -> bool {
+        !self.moved.is_empty() || !self.missing.is_empty()
+    }
+
+    pub fn summary(&self, name: &str) -> String {
+        let mut summary = format!("Popped {} windows from '{}'", self.restored.len(), name);
+        if !self.moved.is_empty() {
+            summary += &format!(", {} moved elsewhere", self.moved.len());
+        }
+        if !self.missing.is_empty() {
+            summary += &format!(", {} closed", self.missing.len());
+        }
+        summary
+    }
+
+    pub fn print_summary(&self) {
+        if !self.workspaces.is_empty() {
+            eprintln!("Stashed workspaces were popped onto:");
+            for (old, new) in self.workspaces.iter() {
+                eprintln!("\t{} -> {}", old, new);
+            }
+        }
+        if !self.moved.is_empty() {
+            eprintln!("The following windows were moved out of the stash workspace:");
+            for (address, workspace) in self.moved.iter() {
+                eprintln!("\t{} (now on workspace {})", address, workspace);
+            }
+        }
+        if !self.missing.is_empty() {
+            eprintln!("The following windows no longer exist:");
+            for address in self.missing.iter() {
+                eprintln!("\t{}", address);
+            }
+        }
+    }
+
+    pub fn print(&self) {
+        self.print_summary();
+        print_dispatch_errors(&self.errors);
+        for warning in self.warnings.iter() {
+            eprintln!("{}", warning);
+        }
+    }
+}
+
+pub fn print_dispatch_errors(errors: &[String]) {
+    if !errors.is_empty() {
+        eprintln!("Completed operation, but with the following dispatch errors:");
+        for error in errors {
+            eprintln!("\t{}", error)
+        }
+    }
+}
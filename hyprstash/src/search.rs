@@ -0,0 +1,57 @@
+use super::*;
+
+#[derive(Serialize, Debug)]
+pub struct SearchMatch {
+    pub name: String,
+    pub matched: Vec<String>,
+}
+
+pub fn list_by_tags(tags: &[String]) -> Result<Vec<String>> {
+    let mut names = StashedInstance::list_instances()?;
+    // Only filtering needs the stashes themselves, unreadable ones can't carry tags.
+    if !tags.is_empty() {
+        names.retain(|name| {
+            StashedInstance::read_with_meta(name)
+                .is_ok_and(|(_, meta)| tags.iter().all(|tag| meta.tags.contains(tag)))
+        });
+    }
+    names.sort();
+    Ok(names)
+}
+
+pub fn find_stashes(query: &str) -> Result<Vec<SearchMatch>> {
+    let query = query.to_lowercase();
+    let is_match = |field: &str| field.to_lowercase().contains(&query);
+
+    let mut matches = vec![];
+    for name in StashedInstance::list_instances()? {
+        let Ok((instance, meta)) = StashedInstance::read_with_meta(&name) else {
+            continue;
+        };
+
+        let mut matched = vec![];
+        if is_match(&name) {
+            matched.push("name".to_owned());
+        }
+        for tag in meta.tags.iter().filter(|tag| is_match(tag)) {
+            matched.push(format!("tag {}", tag));
+        }
+        if let Some(note) = meta.note.as_ref()
+            && is_match(note)
+        {
+            matched.push(format!("note \"{}\"", note));
+        }
+        for client in instance.clients() {
+            if is_match(&client.class) || is_match(&client.title) {
+                matched.push(format!("window {}: {}", client.class, client.title));
+            }
+        }
+
+        if !matched.is_empty() {
+            matches.push(SearchMatch { name, matched });
+        }
+    }
+    matches.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(matches)
+}
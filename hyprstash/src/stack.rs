@@ -0,0 +1,93 @@
+use super::*;
+
+// Pushed stashes are named `stack/<n>`, the highest `n` being the top of the stack.
+const STACK_PREFIX: &str = "stack/";
+
+fn stack_index(name: &str) -> Option<u64> {
+    name.strip_prefix(STACK_PREFIX)?.parse().ok()
+}
+
+pub fn next_stack_name() -> Result<String> {
+    let top = StashedInstance::list_instances()?
+        .iter()
+        .filter_map(|name| stack_index(name))
+        .max()
+        .unwrap_or(0);
+    Ok(format!("{}{}", STACK_PREFIX, top + 1))
+}
+
+pub fn stack_top() -> Result<String> {
+    StashedInstance::list_instances()?
+        .into_iter()
+        .filter_map(|name| Some((stack_index(&name)?, name)))
+        .max()
+        .map(|(_, name)| name)
+        .ok_or(StashError::EmptyStack.into())
+}
+
+// Newest first, so that stashes of stashes come back in the right order.
+pub fn pop_order() -> Result<Vec<String>> {
+    let mut stashes = vec![];
+    for name in StashedInstance::list_instances()? {
+        // Unreadable stashes go last, failing to pop them is reported along the way.
+        let created_at = StashedInstance::read_with_meta(&name)
+            .map(|(_, meta)| meta.created_at)
+            .unwrap_or_default();
+        stashes.push((created_at, stack_index(&name), name));
+    }
+    stashes.sort();
+    Ok(stashes.into_iter().rev().map(|(_, _, name)| name).collect())
+}
+
+pub fn pop_all(flags: &Policy, config: &Config) -> Result<Outcome> {
+    let mut claimed = HashSet::new();
+    let mut outcome = Outcome::default();
+    for name in pop_order()? {
+        // Every pop changes the compositor state.
+        let res = Data::new().and_then(|data| {
+            let instance = StashedInstance::new_from_name(&name)?;
+            let mut options = PopOptions::from_policy(&config.policy(&name).merge(flags));
+
+            let wanted = instance
+                .stashed_workspaces()
+                .iter()
+                .map(|workspace| workspace.original_workspace)
+                .collect::<Vec<_>>();
+            if let StashedInstance::Workspace(workspace) = &instance
+                && claimed.contains(&workspace.original_workspace)
+            {
+                let target = next_free_workspace(&data, &claimed);
+                outcome.warnings.push(format!(
+                    "Workspace {} was already popped onto, popping {} onto {} instead",
+                    workspace.original_workspace, name, target
+                ));
+                options.workspace_target = Some(target);
+                claimed.insert(target);
+            } else {
+                if wanted.iter().any(|workspace| claimed.contains(workspace)) {
+                    outcome.warnings.push(format!(
+                        "{} shares workspaces with a stash popped before it, their windows are merged",
+                        name
+                    ));
+                }
+                claimed.extend(wanted);
+            }
+
+            pop(&data, &name, instance, &options, config)
+        });
+
+        match res {
+            Ok(report) => outcome.popped.push(report),
+            Err(error) => outcome
+                .errors
+                .push(format!("Failed to pop {}: {}", name, error)),
+        }
+    }
+
+    // Some stashes coming back is a partial success, none is a failure.
+    if outcome.popped.is_empty() && !outcome.errors.is_empty() {
+        Err(StashError::PopAllFailed(outcome.errors).into())
+    } else {
+        Ok(outcome)
+    }
+}
@@ -9,12 +9,15 @@ pub struct StashedFullSession {
 pub fn everything_stash(
     data: &Data,
     stash_workspace: WorkspaceId,
+    excluded: &HashSet<WorkspaceId>,
+    keep: bool,
 ) -> Result<(StashedFullSession, Option<DispatchError>)> {
     let mut monitors = vec![];
     let mut dispatch_errors = DispatchError::default();
 
     for monitor in data.monitors.iter() {
-        let (instance, new_dispatch_errors) = monitor_stash(data, monitor.id, stash_workspace)?;
+        let (instance, new_dispatch_errors) =
+            monitor_stash(data, monitor.id, stash_workspace, excluded, keep)?;
         monitors.push(instance);
         if let Some(new_dispatch_errors) = new_dispatch_errors {
             dispatch_errors.append(new_dispatch_errors);
@@ -32,32 +35,113 @@ pub fn everything_stash(
 pub fn everything_pop(
     data: &Data,
     instance: &StashedFullSession,
-    no_missing_monitors: bool,
-    relative: bool,
-) -> Result<()> {
-    let missing_monitors = instance
-        .monitors
-        .iter()
-        .filter_map(|monitor| {
-            (!data
-                .monitors
-                .iter()
-                .any(|m| m.id == monitor.original_monitor))
-            .then_some(monitor.original_monitor)
-        })
-        .collect::<HashSet<_>>();
+    options: &PopOptions,
+    conflicts: &mut Conflicts,
+) -> Result<PopReport> {
+    let PopOptions {
+        relative,
+        exact,
+        steal,
+        monitor_match,
+        ..
+    } = *options;
+    let missing_monitors = instance.missing_monitors(data, monitor_match);
 
-    if no_missing_monitors && !missing_monitors.is_empty() {
+    if options.no_missing_monitors && !missing_monitors.is_empty() {
         return Err(StashError::MonitorNotFound(*missing_monitors.iter().next().unwrap()).into());
     }
 
+    // Every stashed monitor's workspaces land on the focused one, in order.
+    if options.here {
+        let flattened = instance.flattened(data.active_monitor);
+        return monitor_pop_absolute(
+            data,
+            &flattened,
+            Some(data.active_monitor),
+            Some(data.active_workspace),
+            steal,
+            monitor_match,
+            conflicts,
+        );
+    }
+
+    // Every other mode pops onto the original workspace ids.
+    conflicts.check(
+        data,
+        instance
+            .monitors
+            .iter()
+            .flat_map(|monitor| monitor.original_workspaces()),
+    )?;
+
+    let mut report = PopReport::default();
     for monitor in instance.monitors.iter() {
-        if missing_monitors.contains(&monitor.original_monitor) || relative {
-            monitor_pop_relative(data, monitor, false)?;
+        let target = monitor.resolve_monitor(data, monitor_match);
+        debug!(
+            monitor = monitor.original_monitor,
+            target = target.map_or("missing".to_owned(), |target| target.to_string()),
+            relative = target.is_none() || relative,
+            "session_monitor"
+        );
+        if target.is_none() || relative {
+            report.append(monitor_pop_relative(
+                data, monitor, target, false, steal, conflicts,
+            )?);
+        } else if exact {
+            report.append(monitor_pop_exact(
+                data,
+                monitor,
+                target,
+                steal,
+                monitor_match,
+                conflicts,
+            )?);
         } else {
-            monitor_pop_relative(data, monitor, true)?;
+            report.append(monitor_pop_relative(
+                data, monitor, target, true, steal, conflicts,
+            )?);
+        }
+    }
+
+    Ok(report)
+}
+
+impl StashedFullSession {
+    pub fn missing_monitors(&self, data: &Data, monitor_match: MonitorMatch) -> HashSet<MonitorId> {
+        self.monitors
+            .iter()
+            .filter_map(|monitor| {
+                monitor
+                    .resolve_monitor(data, monitor_match)
+                    .is_none()
+                    .then_some(monitor.original_monitor)
+            })
+            .collect()
+    }
+
+    // The whole session as a single monitor's layout.
+    fn flattened(&self, monitor: MonitorId) -> StashedMonitor {
+        StashedMonitor {
+            workspaces: self
+                .monitors
+                .iter()
+                .flat_map(|monitor| monitor.workspaces.iter().cloned())
+                .collect(),
+            layout: self
+                .monitors
+                .iter()
+                .flat_map(|monitor| monitor.layout.iter().copied())
+                .collect(),
+            original_monitor: monitor,
+            original_monitor_name: None,
+            original_monitor_description: None,
+            stashed_location: self.stash_location,
         }
     }
 
-    Ok(())
+    pub fn remove_clients(&mut self, addresses: &HashSet<Address>) {
+        for monitor in self.monitors.iter_mut() {
+            monitor.remove_clients(addresses);
+        }
+    }
 }
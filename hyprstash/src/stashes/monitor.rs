@@ -5,6 +5,10 @@ pub struct StashedMonitor {
     pub workspaces: Vec<StashedWorkspace>,
     pub layout: Vec<WorkspaceId>,
     pub original_monitor: MonitorId,
+    #[serde(default)]
+    pub original_monitor_name: Option<String>,
+    #[serde(default)]
+    pub original_monitor_description: Option<String>,
     pub stashed_location: WorkspaceId,
 }
 
@@ -12,30 +16,28 @@ pub fn monitor_stash(
     data: &Data,
     monitor: MonitorId,
     stash_workspace: WorkspaceId,
+    excluded: &HashSet<WorkspaceId>,
+    keep: bool,
 ) -> Result<(StashedMonitor, Option<DispatchError>)> {
     // Ensure that monitor does exist.
-    let _ = data
-        .monitors
-        .iter()
-        .find(|m| m.id == monitor)
+    let monitor_info = data
+        .monitor(monitor)
         .ok_or(StashError::MonitorNotFound(monitor))?;
 
-    let workspaces = data
-        .workspaces
+    // Never stash stash locations themselves.
+    let layout = data
+        .workspaces_on(monitor)
         .iter()
-        .filter(|workspace| workspace.monitor_id == Some(monitor))
-        .collect::<Vec<_>>();
-
-    let layout = workspaces
-        .iter()
-        .map(|workspace| workspace.id)
+        .copied()
+        .filter(|workspace| *workspace != stash_workspace && !excluded.contains(workspace))
         .collect::<Vec<_>>();
 
     let mut stashed_workspaces = vec![];
     let mut dispatch_errors = DispatchError::default();
 
-    for workspace in workspaces.iter() {
-        let (instance, new_dispatch_errors) = workspace_stash(data, workspace.id, stash_workspace)?;
+    for workspace in layout.iter() {
+        let (instance, new_dispatch_errors) =
+            workspace_stash(data, *workspace, stash_workspace, keep)?;
         stashed_workspaces.push(instance);
         if let Some(new_dispatch_errors) = new_dispatch_errors {
             dispatch_errors.append(new_dispatch_errors);
@@ -46,6 +48,8 @@ pub fn monitor_stash(
         workspaces: stashed_workspaces,
         layout,
         original_monitor: monitor,
+        original_monitor_name: Some(monitor_info.name.clone()),
+        original_monitor_description: Some(monitor_info.description.clone()),
         stashed_location: stash_workspace,
     };
 
@@ -56,64 +60,209 @@ pub fn monitor_pop_absolute(
     data: &Data,
     instance: &StashedMonitor,
     target: Option<MonitorId>,
-) -> Result<()> {
-    let target = target.unwrap_or(instance.original_monitor);
+    first_workspace: Option<WorkspaceId>,
+    steal: bool,
+    monitor_match: MonitorMatch,
+    conflicts: &mut Conflicts,
+) -> Result<PopReport> {
+    let target = target
+        .or_else(|| instance.resolve_monitor(data, monitor_match))
+        .unwrap_or(instance.original_monitor);
+    debug!(
+        monitor = instance.original_monitor,
+        target,
+        mode = "absolute",
+        "monitor_plan"
+    );
+
+    let old_new_workspace_map = absolute_workspace_map(data, instance, target, first_workspace);
+    debug!(map = ?old_new_workspace_map, "workspace_map");
+    // The layout keeps the workspaces a selection left out, only the popped ones count.
+    conflicts.check(
+        data,
+        instance
+            .workspaces
+            .iter()
+            .map(|workspace| old_new_workspace_map[&workspace.original_workspace]),
+    )?;
+
+    let mut report = PopReport::default();
+    for workspace in instance.workspaces.iter() {
+        let new_workspace = old_new_workspace_map[&workspace.original_workspace];
+        report.append(workspace_pop(
+            data,
+            workspace,
+            Some(new_workspace),
+            steal,
+            conflicts,
+        )?);
+    }
+
+    Ok(report)
+}
+
+// Brings back the original workspace ids, moving them over from wherever they are now.
+pub fn monitor_pop_exact(
+    data: &Data,
+    instance: &StashedMonitor,
+    target: Option<MonitorId>,
+    steal: bool,
+    monitor_match: MonitorMatch,
+    conflicts: &mut Conflicts,
+) -> Result<PopReport> {
+    let target = target
+        .or_else(|| instance.resolve_monitor(data, monitor_match))
+        .unwrap_or(instance.original_monitor);
+    conflicts.check(data, instance.original_workspaces())?;
+
+    let mut report = PopReport::default();
+    for workspace in instance.workspaces.iter() {
+        let popped = workspace_pop(
+            data,
+            workspace,
+            Some(workspace.original_workspace),
+            steal,
+            conflicts,
+        )?;
+        let new_workspace = popped
+            .workspaces
+            .last()
+            .map(|(_, new_workspace)| *new_workspace)
+            .unwrap_or(workspace.original_workspace);
+        report.append(popped);
+
+        if data.workspace_monitor(new_workspace) != Some(target) {
+            let _ = move_workspace_to_monitor(new_workspace, target);
+        }
+    }
 
-    let mut max_workspace = data
+    Ok(report)
+}
+
+// Maps the stashed layout onto the target monitor's workspaces in order, optionally
+// starting at `first_workspace` so that the stash lands where we are.
+fn absolute_workspace_map(
+    data: &Data,
+    instance: &StashedMonitor,
+    target: MonitorId,
+    first_workspace: Option<WorkspaceId>,
+) -> HashMap<WorkspaceId, WorkspaceId> {
+    let mut next_workspace = data
         .workspaces
         .iter()
         .filter_map(|w| (w.id != instance.stashed_location).then_some(w.id))
         .max()
-        .unwrap_or(WorkspaceId::default());
-    let monitor_workspaces = data
-        .workspaces
+        .unwrap_or(WorkspaceId::default())
+        + 1;
+    let mut monitor_workspaces = data
+        .workspaces_on(target)
         .iter()
-        .filter_map(|w| {
-            (w.monitor_id == Some(target) && w.id != instance.stashed_location).then_some(w.id)
-        })
+        .copied()
+        // Special workspaces have negative ids, stashed windows don't belong there.
+        .filter(|w| *w > 0 && *w != instance.stashed_location)
         .collect::<Vec<_>>();
-    let old_new_workspace_map = instance
+    monitor_workspaces.sort();
+
+    if let Some(first_workspace) = first_workspace {
+        let start = monitor_workspaces
+            .iter()
+            .position(|w| *w == first_workspace)
+            .unwrap_or(0);
+        monitor_workspaces.drain(..start);
+    }
+
+    instance
         .layout
         .iter()
         .enumerate()
         .map(|(idx, workspace)| {
-            let old = monitor_workspaces
-                .get(idx)
-                .copied()
-                .unwrap_or(max_workspace);
-            max_workspace += 1;
-            (*workspace, old)
+            // Extra workspaces are created past every existing one.
+            let new = monitor_workspaces.get(idx).copied().unwrap_or_else(|| {
+                next_workspace += 1;
+                next_workspace - 1
+            });
+            (*workspace, new)
         })
-        .collect::<HashMap<_, _>>();
-
-    for workspace in instance.workspaces.iter() {
-        let new_workspace = old_new_workspace_map[&workspace.original_workspace];
-        workspace_pop(data, workspace, Some(new_workspace))?;
-    }
-
-    Ok(())
+        .collect::<HashMap<_, _>>()
 }
 
 pub fn monitor_pop_relative(
     data: &Data,
     instance: &StashedMonitor,
+    target: Option<MonitorId>,
     always_move_to_monitor: bool,
-) -> Result<()> {
+    steal: bool,
+    conflicts: &mut Conflicts,
+) -> Result<PopReport> {
+    let target = target.unwrap_or(instance.original_monitor);
+    debug!(
+        monitor = instance.original_monitor,
+        target,
+        mode = "relative",
+        "monitor_plan"
+    );
+    conflicts.check(data, instance.original_workspaces())?;
+
+    let mut report = PopReport::default();
     for workspace in instance.workspaces.iter() {
-        // If that workspace does not exist, move the new workspace to that monitor.
-        let move_to_monitor = (!data
+        let popped = workspace_pop(data, workspace, None, steal, conflicts)?;
+        let new_workspace = popped
             .workspaces
+            .last()
+            .map(|(_, new_workspace)| *new_workspace)
+            .unwrap_or(workspace.original_workspace);
+        report.append(popped);
+
+        // If that workspace did not exist, move the new workspace to that monitor.
+        if !data.has_workspace(new_workspace) || always_move_to_monitor {
+            let _ = move_workspace_to_monitor(new_workspace, target);
+        }
+    }
+
+    Ok(report)
+}
+
+impl StashedMonitor {
+    // Monitor ids are handed out in connection order, so names or descriptions are
+    // more reliable when displays get plugged in and out.
+    pub fn resolve_monitor(&self, data: &Data, monitor_match: MonitorMatch) -> Option<MonitorId> {
+        let found = match monitor_match {
+            MonitorMatch::Id => None,
+            MonitorMatch::Name => self.original_monitor_name.as_ref().map(|name| {
+                data.monitors
+                    .iter()
+                    .find_map(|m| (&m.name == name).then_some(m.id))
+            }),
+            MonitorMatch::Description => {
+                self.original_monitor_description
+                    .as_ref()
+                    .map(|description| {
+                        data.monitors
+                            .iter()
+                            .find_map(|m| (&m.description == description).then_some(m.id))
+                    })
+            }
+        };
+
+        // Fall back to ids for stashes that predate names and descriptions.
+        found.unwrap_or_else(|| data.monitor(self.original_monitor).map(|m| m.id))
+    }
+
+    pub fn original_workspaces(&self) -> impl Iterator<Item = WorkspaceId> {
+        self.workspaces
             .iter()
-            .any(|w| w.id == workspace.original_workspace))
-            || always_move_to_monitor;
+            .map(|workspace| workspace.original_workspace)
+    }
 
-        workspace_pop(data, workspace, None)?;
+    pub fn is_called(&self, wanted: &str) -> bool {
+        self.original_monitor.to_string() == wanted
+            || self.original_monitor_name.as_deref() == Some(wanted)
+            || self.original_monitor_description.as_deref() == Some(wanted)
+    }
 
-        if move_to_monitor {
-            let _ =
-                move_workspace_to_monitor(workspace.original_workspace, instance.original_monitor);
+    pub fn remove_clients(&mut self, addresses: &HashSet<Address>) {
+        for workspace in self.workspaces.iter_mut() {
+            workspace.remove_clients(addresses);
         }
     }
-
-    Ok(())
 }
@@ -1,65 +1,285 @@
 use super::*;
 
+use hyprland::{data::Client, keyword::Keyword};
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StashedWorkspace {
     pub stash_location: WorkspaceId,
     pub original_workspace: WorkspaceId,
     pub client_addresses: Vec<Address>,
+    #[serde(default)]
+    pub clients: Vec<StashedClient>,
+    // Workspace rule options as passed to `keyword workspace`, e.g. `gapsin` and `5`.
+    #[serde(default)]
+    pub rules: Vec<(String, String)>,
+}
+
+// Workspace rule fields as reported by `workspacerules`, and their `keyword` names.
+// `monitor` and `default` are left out, the pop decides where the workspace goes.
+const RULE_OPTIONS: &[(&str, &str)] = &[
+    ("gapsIn", "gapsin"),
+    ("gapsOut", "gapsout"),
+    ("borderSize", "bordersize"),
+    ("border", "border"),
+    ("shadow", "shadow"),
+    ("rounding", "rounding"),
+    ("decorate", "decorate"),
+    ("persistent", "persistent"),
+    ("defaultName", "defaultName"),
+    ("layout", "layout"),
+];
+
+fn workspace_rule_options(data: &Data, workspace: WorkspaceId) -> Vec<(String, String)> {
+    let mut options = vec![];
+    for rule in data.workspace_rules_for(workspace) {
+        for (field, option) in RULE_OPTIONS {
+            let value = match &rule[*field] {
+                serde_json::Value::Null => continue,
+                serde_json::Value::String(value) => value.clone(),
+                serde_json::Value::Array(values) => values
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                value => value.to_string(),
+            };
+            options.push((option.to_string(), value));
+        }
+    }
+    options
+}
+
+fn apply_workspace_rules(
+    workspace: WorkspaceId,
+    rules: &[(String, String)],
+) -> Option<DispatchError> {
+    if rules.is_empty() {
+        return None;
+    }
+
+    let options = rules
+        .iter()
+        .map(|(option, value)| format!("{}:{}", option, value))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let value = format!("{}, {}", workspace, options);
+    timed("keyword", format!("workspace {}", value), || {
+        Keyword::set("workspace", value)
+    })
+    .err()
+    .map(|error| DispatchError(vec![error]))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StashedClient {
+    pub address: Address,
+    pub class: String,
+    pub title: String,
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default, skip_serializing_if = "WindowState::is_default")]
+    pub state: WindowState,
 }
 
 pub fn workspace_stash(
     data: &Data,
     workspace: WorkspaceId,
     stash_workspace: WorkspaceId,
+    keep: bool,
+) -> Result<(StashedWorkspace, Option<DispatchError>)> {
+    let real_clients = data.clients_on(workspace).collect::<Vec<_>>();
+    stash_clients(data, workspace, real_clients, stash_workspace, keep)
+}
+
+fn stash_clients(
+    data: &Data,
+    workspace: WorkspaceId,
+    real_clients: Vec<&Client>,
+    stash_workspace: WorkspaceId,
+    keep: bool,
 ) -> Result<(StashedWorkspace, Option<DispatchError>)> {
-    let clients = &data.clients;
+    debug!(
+        workspace,
+        clients = real_clients.len(),
+        stash_location = stash_workspace,
+        "workspace_stash"
+    );
+    let clients = real_clients
+        .iter()
+        .zip(window_states(&real_clients))
+        .map(|(client, state)| StashedClient {
+            address: client.address.clone(),
+            class: client.class.clone(),
+            title: client.title.clone(),
+            command: client_command(client.pid),
+            state,
+        })
+        .collect::<Vec<_>>();
     let client_addresses = clients
         .iter()
-        .filter_map(|client| (client.workspace.id == workspace).then_some(client.address.clone()))
+        .map(|client| client.address.clone())
         .collect::<Vec<_>>();
 
-    let dispatch_error = move_clients_to_workspace(stash_workspace, &client_addresses);
+    // Kept stashes only record where everything is.
+    let mut dispatch_errors = DispatchError::default();
+    if !keep {
+        if let Some(error) = unpin_clients(&real_clients) {
+            dispatch_errors.append(error);
+        }
+        if let Some(error) = move_clients_to_workspace(stash_workspace, &client_addresses) {
+            dispatch_errors.append(error);
+        }
+    }
+    let dispatch_error = dispatch_errors.into_optional();
 
     let stashed = StashedWorkspace {
         stash_location: stash_workspace,
         original_workspace: workspace,
         client_addresses,
+        clients,
+        rules: workspace_rule_options(data, workspace),
     };
 
     Ok((stashed, dispatch_error))
 }
 
+// How a pop treats workspaces that have windows of their own by now.
+#[derive(Debug, Default)]
+pub struct Conflicts {
+    pub policy: ConflictPolicy,
+    // Where `swap` stashes occupants, resolved like any other stash location.
+    pub stash_location: WorkspaceId,
+    pub collision: CollisionPolicy,
+    // Never picked by `next-free`, as this pop already uses or will use them.
+    pub taken: HashSet<WorkspaceId>,
+    // Windows of the stash being popped, which never count as occupants.
+    pub stashed: HashSet<Address>,
+    // Occupants stashed by `swap`, for the pop report.
+    pub warnings: Vec<String>,
+}
+
+impl Conflicts {
+    fn occupants<'a>(
+        &'a self,
+        data: &'a Data,
+        target: WorkspaceId,
+    ) -> impl Iterator<Item = &'a Client> {
+        data.clients_on(target)
+            .filter(|client| !self.stashed.contains(&client.address))
+    }
+
+    // `fail` has to refuse before the first window of a multi-workspace pop is moved.
+    pub fn check(&self, data: &Data, targets: impl IntoIterator<Item = WorkspaceId>) -> Result<()> {
+        if self.policy != ConflictPolicy::Fail {
+            return Ok(());
+        }
+        match targets
+            .into_iter()
+            .find(|target| self.occupants(data, *target).next().is_some())
+        {
+            Some(target) => Err(StashError::WorkspaceOccupied(target).into()),
+            None => Ok(()),
+        }
+    }
+
+    fn resolve(&mut self, data: &Data, target: WorkspaceId) -> Result<WorkspaceId> {
+        let occupants = self.occupants(data, target).collect::<Vec<_>>();
+        if occupants.is_empty() {
+            self.taken.insert(target);
+            return Ok(target);
+        }
+        debug!(workspace = target, policy = ?self.policy, "conflict");
+
+        match self.policy {
+            ConflictPolicy::Merge => Ok(target),
+            ConflictPolicy::Fail => Err(StashError::WorkspaceOccupied(target).into()),
+            ConflictPolicy::NextFree => {
+                let free = next_free_workspace(data, &self.taken);
+                self.taken.insert(free);
+                Ok(free)
+            }
+            ConflictPolicy::Swap => {
+                // Written right away, so the windows are never lost track of.
+                let name = next_stack_name()?;
+                let location = StashRegistry::load()?.resolve_location(
+                    data,
+                    self.stash_location,
+                    self.collision,
+                )?;
+                let (occupants, dispatch_error) =
+                    stash_clients(data, target, occupants, location, false)?;
+                StashedInstance::Workspace(occupants)
+                    .write_with_meta(&name, StashMeta::new(None)?)?;
+                self.warnings.push(format!(
+                    "Stashed the windows on workspace {} as {}",
+                    target, name
+                ));
+                if let Some(error) = dispatch_error {
+                    return Err(StashError::Dispatch(error).into());
+                }
+                Ok(target)
+            }
+        }
+    }
+}
+
 pub fn workspace_pop(
     data: &Data,
     instance: &StashedWorkspace,
     target: Option<WorkspaceId>,
-) -> Result<()> {
-    let target = target.unwrap_or(instance.original_workspace);
-    let existing_clients = &data.clients;
-    let existing_clients = existing_clients
-        .iter()
-        .map(|client| (client.address.clone(), client))
-        .collect::<HashMap<_, _>>();
-    let valid_clients = instance
-        .client_addresses
-        .iter()
-        .filter(|client| {
-            if let Some(real_client) = existing_clients.get(client)
-                && real_client.workspace.id == instance.stash_location
-            {
-                true
-            } else {
-                false
+    steal: bool,
+    conflicts: &mut Conflicts,
+) -> Result<PopReport> {
+    let original = target.unwrap_or(instance.original_workspace);
+    let target = conflicts.resolve(data, original)?;
+
+    let mut report = PopReport::default();
+    for client in instance.client_addresses.iter() {
+        match data.client(client) {
+            Some(real_client) if real_client.workspace.id == instance.stash_location || steal => {
+                trace!(
+                    address = %client,
+                    class = real_client.class,
+                    "client_restored"
+                );
+                report.restored.push(client.clone())
             }
-        })
-        .cloned()
-        .collect::<Vec<_>>();
+            Some(real_client) => {
+                trace!(
+                    address = %client,
+                    workspace = real_client.workspace.id,
+                    "client_moved"
+                );
+                report
+                    .moved
+                    .push((client.clone(), real_client.workspace.id))
+            }
+            None => {
+                trace!(address = %client, "client_missing");
+                report.missing.push(client.clone())
+            }
+        }
+    }
+
+    report
+        .workspaces
+        .push((instance.original_workspace, target));
+    if let Some(error) = move_clients_to_workspace(target, &report.restored) {
+        return Err(StashError::Dispatch(error).into());
+    }
+    // The windows are back either way, a rule that didn't apply only makes the pop partial.
+    if let Some(errors) = apply_workspace_rules(target, &instance.rules) {
+        report.errors = errors.messages();
+    }
 
-    let dispatch_error = move_clients_to_workspace(target, &valid_clients);
+    Ok(report)
+}
 
-    if let Some(error) = dispatch_error {
-        Err(StashError::Dispatch(error).into())
-    } else {
-        Ok(())
+impl StashedWorkspace {
+    pub fn remove_clients(&mut self, addresses: &HashSet<Address>) {
+        self.client_addresses
+            .retain(|address| !addresses.contains(address));
+        self.clients
+            .retain(|client| !addresses.contains(&client.address));
     }
 }
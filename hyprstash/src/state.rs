@@ -1,8 +1,17 @@
 use super::*;
 
-use std::fs;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-const STASH_PATH: &str = "/tmp/hyprstash/";
+const DEFAULT_STORE_PATH: &str = "/tmp/hyprstash/";
+// Where stashes go when not running under Hyprland, `%` never starts an encoded signature.
+const NO_INSTANCE_DIR: &str = "%none";
+
+static STORE_PATH: OnceLock<PathBuf> = OnceLock::new();
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum StashedInstance {
@@ -11,7 +20,83 @@ pub enum StashedInstance {
     Everything(StashedFullSession),
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct StashMeta {
+    pub created_at: u64,
+    pub expires_at: Option<u64>,
+    pub tags: Vec<String>,
+    pub note: Option<String>,
+    // Made with `--keep`, the windows were left where they are.
+    pub keep: bool,
+}
+
+impl StashMeta {
+    pub fn new(expire: Option<Duration>) -> Result<Self> {
+        let now = unix_now();
+        let expires_at = match expire {
+            Some(expire) => Some(
+                now.checked_add(expire.as_secs())
+                    .ok_or_else(|| StashError::BadDuration("expiry is too far out".to_owned()))?,
+            ),
+            None => None,
+        };
+        Ok(StashMeta {
+            created_at: now,
+            expires_at,
+            ..Default::default()
+        })
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= unix_now())
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct StashFile {
+    meta: StashMeta,
+    instance: StashedInstance,
+}
+
 impl StashedInstance {
+    // Must be called before touching the store, otherwise the default is used.
+    // Each Hyprland instance gets its own part of the store.
+    pub fn set_store_path(base: Option<PathBuf>, signature: Option<&str>) {
+        let base = base.unwrap_or_else(|| PathBuf::from(DEFAULT_STORE_PATH));
+        let path = match signature {
+            Some(signature) => base.join(encode_name(signature)),
+            None => base.join(NO_INSTANCE_DIR),
+        };
+        let _ = STORE_PATH.set(path);
+    }
+
+    // Old stashes belong to the running Hyprland instance, so this is only called once
+    // its signature is known, never from a shell outside Hyprland or for completions.
+    pub fn adopt_unpartitioned(base: Option<PathBuf>) {
+        let base = base.unwrap_or_else(|| PathBuf::from(DEFAULT_STORE_PATH));
+        let path = Self::store_path();
+        if let Err(error) = migrate_unpartitioned(&base, path) {
+            eprintln!(
+                "Could not move old stashes into {}: {}",
+                path.display(),
+                error
+            );
+        }
+    }
+
+    pub fn store_path() -> &'static Path {
+        STORE_PATH.get_or_init(|| PathBuf::from(DEFAULT_STORE_PATH))
+    }
+
     pub fn check_already_stashed(name: &str) -> Result<()> {
         let path = Self::stash_path(name)?;
         (!fs::exists(&path)?)
@@ -20,48 +105,443 @@ impl StashedInstance {
     }
 
     pub fn list_instances() -> Result<Vec<String>> {
-        let list = fs::read_dir(STASH_PATH)?
-            .map(|entry| Ok(entry?.file_name().to_str().unwrap().to_string()))
-            .collect::<Result<Vec<_>>>()?;
+        Self::setup_directories()?;
+
+        let mut list = vec![];
+        for entry in fs::read_dir(Self::store_path())? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                continue;
+            }
+            // Anything that doesn't decode was not written by us.
+            if let Some(name) = entry.file_name().to_str().and_then(decode_name) {
+                list.push(name);
+            }
+        }
         Ok(list)
     }
 
+    pub fn store_fingerprint() -> Vec<(String, Option<SystemTime>)> {
+        fs::read_dir(Self::store_path())
+            .map(|entries| {
+                let mut fingerprint = entries
+                    .filter_map(|entry| {
+                        let entry = entry.ok()?;
+                        let modified = entry.metadata().and_then(|m| m.modified()).ok();
+                        Some((entry.file_name().to_string_lossy().to_string(), modified))
+                    })
+                    .collect::<Vec<_>>();
+                fingerprint.sort();
+                fingerprint
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Workspace(_) => "workspace",
+            Self::Monitor(_) => "monitor",
+            Self::Everything(_) => "everything",
+        }
+    }
+
+    pub fn clients(&self) -> Vec<&StashedClient> {
+        match self {
+            Self::Workspace(instance) => instance.clients.iter().collect(),
+            Self::Monitor(instance) => instance
+                .workspaces
+                .iter()
+                .flat_map(|workspace| workspace.clients.iter())
+                .collect(),
+            Self::Everything(instance) => instance
+                .monitors
+                .iter()
+                .flat_map(|monitor| monitor.workspaces.iter())
+                .flat_map(|workspace| workspace.clients.iter())
+                .collect(),
+        }
+    }
+
+    pub fn stashed_workspaces(&self) -> Vec<&StashedWorkspace> {
+        match self {
+            Self::Workspace(instance) => vec![instance],
+            Self::Monitor(instance) => instance.workspaces.iter().collect(),
+            Self::Everything(instance) => instance
+                .monitors
+                .iter()
+                .flat_map(|monitor| monitor.workspaces.iter())
+                .collect(),
+        }
+    }
+
+    pub fn client_addresses(&self) -> Vec<&Address> {
+        match self {
+            Self::Workspace(instance) => instance.client_addresses.iter().collect(),
+            Self::Monitor(instance) => instance
+                .workspaces
+                .iter()
+                .flat_map(|workspace| workspace.client_addresses.iter())
+                .collect(),
+            Self::Everything(instance) => instance
+                .monitors
+                .iter()
+                .flat_map(|monitor| monitor.workspaces.iter())
+                .flat_map(|workspace| workspace.client_addresses.iter())
+                .collect(),
+        }
+    }
+
+    // Rewriting an existing stash keeps its metadata.
     pub fn write(self, name: &str) -> Result<()> {
+        let meta = match Self::read_with_meta(name) {
+            Ok((_, meta)) => meta,
+            Err(_) => StashMeta::new(None)?,
+        };
+        self.write_with_meta(name, meta)
+    }
+
+    pub fn write_with_meta(self, name: &str, meta: StashMeta) -> Result<()> {
         Self::setup_directories()?;
 
-        let data = serde_json::to_string(&self)?;
+        let file = StashFile {
+            meta,
+            instance: self,
+        };
+        let data = serde_json::to_string(&file)?;
         fs::write(Self::stash_path(name)?, data).map_err(StashError::IOError)?;
 
         Ok(())
     }
 
     pub fn new_from_name(name: &str) -> Result<Self> {
-        let data = fs::read_to_string(Self::stash_path(name)?)?;
-        let stashed = serde_json::from_str(&data)?;
+        let (stashed, _) = Self::read_with_meta(name)?;
+        Ok(stashed)
+    }
+
+    pub fn read_with_meta(name: &str) -> Result<(Self, StashMeta)> {
+        let data = fs::read_to_string(Self::existing_stash_path(name)?)?;
+        // Stashes written before metadata existed are bare instances. Untagged enums
+        // can't be used here since serde can't buffer the i128 monitor ids.
+        let stashed = match serde_json::from_str::<StashFile>(&data) {
+            Ok(file) => (file.instance, file.meta),
+            Err(_) => (serde_json::from_str(&data)?, StashMeta::default()),
+        };
         Ok(stashed)
     }
 
+    pub fn read_as_json(name: &str) -> Result<serde_json::Value> {
+        let (instance, meta) = Self::read_with_meta(name)?;
+        Ok(serde_json::to_value(StashFile { meta, instance })?)
+    }
+
     pub fn remove_instance(name: &str) {
         if let Ok(path) = Self::stash_path(name) {
             let _ = fs::remove_file(path);
         }
     }
 
+    // Keep whatever could not be restored, or was not selected, around instead of
+    // losing track of it.
+    pub fn finish_pop(
+        mut self,
+        name: &str,
+        report: &mut PopReport,
+        force: bool,
+        only: &PopSelection,
+    ) -> Result<()> {
+        let restored = report.restored.iter().cloned().collect::<HashSet<_>>();
+        self.remove_clients(&restored);
+        self.remove_selected(only, !force);
+
+        if self.client_addresses().is_empty() {
+            Self::remove_instance(name);
+            return Ok(());
+        }
+
+        if report.is_partial() && !force {
+            report.warnings.push(format!(
+                "Stash {} was only partially restored, keeping it (use --force to remove)",
+                name
+            ));
+        }
+        self.write(name)
+    }
+
+    // The parts of the stash a pop should restore.
+    pub fn select(&self, only: &PopSelection) -> Result<Self> {
+        let mut selected = self.clone();
+        match &mut selected {
+            Self::Workspace(_) if !only.is_empty() => {
+                return Err(StashError::BadSelection.into());
+            }
+            Self::Workspace(_) => {}
+            Self::Monitor(instance) => {
+                if !only.matches_monitor(instance) {
+                    instance.workspaces.clear();
+                }
+                instance
+                    .workspaces
+                    .retain(|workspace| only.matches_workspace(workspace));
+            }
+            Self::Everything(instance) => {
+                instance
+                    .monitors
+                    .retain(|monitor| only.matches_monitor(monitor));
+                for monitor in instance.monitors.iter_mut() {
+                    monitor
+                        .workspaces
+                        .retain(|workspace| only.matches_workspace(workspace));
+                }
+                instance
+                    .monitors
+                    .retain(|monitor| !monitor.workspaces.is_empty());
+            }
+        }
+
+        if !only.is_empty() && selected.stashed_workspaces().is_empty() {
+            return Err(StashError::EmptySelection.into());
+        }
+        Ok(selected)
+    }
+
+    // The layout is left alone, so absolute pops of the rest still line up.
+    fn remove_selected(&mut self, only: &PopSelection, only_empty: bool) {
+        let remove = |monitor: &StashedMonitor, workspace: &StashedWorkspace| {
+            only.matches_monitor(monitor)
+                && only.matches_workspace(workspace)
+                && (!only_empty || workspace.client_addresses.is_empty())
+        };
+        match self {
+            // A workspace stash is always popped whole.
+            Self::Workspace(instance) if !only_empty => {
+                instance.client_addresses.clear();
+                instance.clients.clear();
+            }
+            Self::Workspace(_) => {}
+            Self::Monitor(instance) => {
+                let monitor = instance.clone();
+                instance
+                    .workspaces
+                    .retain(|workspace| !remove(&monitor, workspace));
+            }
+            Self::Everything(instance) => {
+                for monitor in instance.monitors.iter_mut() {
+                    let original = monitor.clone();
+                    monitor
+                        .workspaces
+                        .retain(|workspace| !remove(&original, workspace));
+                }
+                instance
+                    .monitors
+                    .retain(|monitor| !monitor.workspaces.is_empty());
+            }
+        }
+    }
+
+    pub fn remove_clients(&mut self, addresses: &HashSet<Address>) {
+        match self {
+            Self::Workspace(instance) => instance.remove_clients(addresses),
+            Self::Monitor(instance) => instance.remove_clients(addresses),
+            Self::Everything(instance) => instance.remove_clients(addresses),
+        }
+    }
+
     pub fn remove_all_instances() {
-        let _ = fs::remove_dir_all(STASH_PATH);
+        let _ = fs::remove_dir_all(Self::store_path());
         let _ = Self::setup_directories();
     }
 
-    fn stash_path(name: &str) -> Result<String> {
-        if name.chars().all(char::is_alphanumeric) {
-            Ok(format!("{}/{}", STASH_PATH, name))
+    pub fn rename_instance(old: &str, new: &str) -> Result<()> {
+        let old_path = Self::existing_stash_path(old)?;
+        Self::check_already_stashed(new)?;
+        fs::rename(old_path, Self::stash_path(new)?)?;
+        Ok(())
+    }
+
+    pub fn copy_instance(source: &str, destination: &str) -> Result<()> {
+        let source_path = Self::existing_stash_path(source)?;
+        Self::check_already_stashed(destination)?;
+        fs::copy(source_path, Self::stash_path(destination)?)?;
+        Ok(())
+    }
+
+    fn stash_path(name: &str) -> Result<PathBuf> {
+        if is_valid_name(name) {
+            Ok(Self::store_path().join(encode_name(name)))
         } else {
             Err(StashError::BadName.into())
         }
     }
 
+    fn existing_stash_path(name: &str) -> Result<PathBuf> {
+        let path = Self::stash_path(name)?;
+        if fs::exists(&path)? {
+            Ok(path)
+        } else {
+            Err(StashError::StashNotFound(name.to_owned()).into())
+        }
+    }
+
     fn setup_directories() -> Result<()> {
-        fs::create_dir_all(STASH_PATH)?;
+        fs::create_dir_all(Self::store_path())?;
         Ok(())
     }
 }
+
+// Stashes from before the store was partitioned sit right in the base directory, they
+// are moved into the partition in use so they don't get lost.
+fn migrate_unpartitioned(base: &Path, partition: &Path) -> Result<()> {
+    let Ok(entries) = fs::read_dir(base) else {
+        return Ok(());
+    };
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let file_name = entry.file_name();
+        let Some(name) = file_name.to_str().and_then(decode_name) else {
+            continue;
+        };
+        fs::create_dir_all(partition)?;
+        let destination = partition.join(&file_name);
+        if fs::exists(&destination)? {
+            eprintln!(
+                "Not moving old stash {}, {} already has one",
+                name,
+                partition.display()
+            );
+            continue;
+        }
+        fs::rename(entry.path(), destination)?;
+    }
+    Ok(())
+}
+
+// Names may use `/` to namespace stashes, e.g. `proj/frontend`.
+fn is_valid_name(name: &str) -> bool {
+    let allowed = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ' ' | '/');
+    name.chars().all(allowed)
+        && name
+            .split('/')
+            .all(|segment| !segment.trim().is_empty() && segment != "." && segment != "..")
+}
+
+// Stashes are stored flat, so everything that could be meaningful to the filesystem
+// (separators, dots, spaces) is percent encoded.
+fn encode_name(name: &str) -> String {
+    let mut encoded = String::new();
+    for c in name.chars() {
+        if c.is_alphanumeric() || matches!(c, '-' | '_') {
+            encoded.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                encoded += &format!("%{:02X}", byte);
+            }
+        }
+    }
+    encoded
+}
+
+fn decode_name(encoded: &str) -> Option<String> {
+    let mut bytes = vec![];
+    let mut chars = encoded.bytes();
+    while let Some(byte) = chars.next() {
+        if byte == b'%' {
+            let hex = [chars.next()?, chars.next()?];
+            bytes.push(u8::from_str_radix(str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    let name = String::from_utf8(bytes).ok()?;
+    (is_valid_name(&name) && encode_name(&name) == encoded).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_stay_in_the_store() {
+        for name in [
+            "..", ".", "a/../b", "a/./b", "/a", "a/", "a//b", " ", "", "a\\b",
+        ] {
+            assert!(!is_valid_name(name), "{:?} should be rejected", name);
+        }
+        for name in ["work", "proj/frontend", "v1.2", "my stash", "a-b_c"] {
+            assert!(is_valid_name(name), "{:?} should be accepted", name);
+        }
+    }
+
+    #[test]
+    fn encoded_names_are_flat() {
+        assert_eq!(encode_name("proj/frontend"), "proj%2Ffrontend");
+        assert_eq!(encode_name("a/../b"), "a%2F%2E%2E%2Fb");
+        assert_eq!(encode_name("my stash"), "my%20stash");
+        for name in ["proj/frontend", "..", "a/../b", "/a"] {
+            assert!(!encode_name(name).contains(['/', '.']));
+        }
+    }
+
+    #[test]
+    fn percent_characters() {
+        assert!(!is_valid_name("100%"));
+        assert_eq!(decode_name("100%"), None);
+        assert_eq!(decode_name("a%2"), None);
+        assert_eq!(decode_name("a%zz"), None);
+        // Percent encoded escapes are rejected just like literal ones.
+        assert_eq!(decode_name("%2E%2E"), None);
+        assert_eq!(decode_name("a%2F%2E%2E%2Fb"), None);
+        assert_eq!(decode_name("%2Fa"), None);
+        // A literal `%` in a name would decode ambiguously.
+        assert_eq!(decode_name("a%25b"), None);
+    }
+
+    #[test]
+    fn lowercase_hex_is_not_canonical() {
+        assert_eq!(
+            decode_name("proj%2Ffrontend").as_deref(),
+            Some("proj/frontend")
+        );
+        assert_eq!(decode_name("proj%2ffrontend"), None);
+    }
+
+    #[test]
+    fn expiry_overflow() {
+        assert!(StashMeta::new(Some(Duration::from_secs(u64::MAX))).is_err());
+        let meta = StashMeta::new(Some(Duration::from_secs(60))).unwrap();
+        assert_eq!(meta.expires_at, Some(meta.created_at + 60));
+        assert!(!meta.is_expired());
+    }
+
+    #[test]
+    fn forced_workspace_pops_remove_everything() {
+        let mut instance = StashedInstance::Workspace(StashedWorkspace {
+            stash_location: 8,
+            original_workspace: 1,
+            client_addresses: vec![Address::new("0x1")],
+            clients: vec![],
+            rules: vec![],
+        });
+        let only = PopSelection::default();
+        instance.remove_selected(&only, true);
+        assert_eq!(instance.client_addresses().len(), 1);
+        instance.remove_selected(&only, false);
+        assert!(instance.client_addresses().is_empty());
+    }
+
+    #[test]
+    fn non_ascii_names_round_trip() {
+        for name in ["Büro", "日本語/メモ", "ünïcödé stash"] {
+            let encoded = encode_name(name);
+            assert_eq!(
+                decode_name(&encoded).as_deref(),
+                Some(name),
+                "{:?}",
+                encoded
+            );
+        }
+    }
+}
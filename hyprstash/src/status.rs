@@ -0,0 +1,121 @@
+use super::*;
+
+use hyprland::event_listener::EventListener;
+use std::{sync::mpsc, thread, time::Duration};
+
+const STORE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum StatusFormat {
+    #[default]
+    Plain,
+    Waybar,
+}
+
+#[derive(Serialize, Debug)]
+struct WaybarStatus {
+    text: String,
+    tooltip: String,
+    class: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct StatusEntry {
+    name: String,
+    kind: &'static str,
+    windows: usize,
+}
+
+pub fn status_entries() -> Result<Vec<StatusEntry>> {
+    let mut stashes = vec![];
+    for name in StashedInstance::list_instances()? {
+        // Stashes may be removed while we are reading them.
+        if let Ok(instance) = StashedInstance::new_from_name(&name) {
+            stashes.push(StatusEntry {
+                name,
+                kind: instance.kind(),
+                windows: instance.client_addresses().len(),
+            });
+        }
+    }
+    stashes.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(stashes)
+}
+
+pub fn print_status(format: StatusFormat) -> Result<()> {
+    let stashes = status_entries()?;
+    let lines = stashes
+        .iter()
+        .map(|stash| format!("{} ({}, {} windows)", stash.name, stash.kind, stash.windows))
+        .collect::<Vec<_>>();
+
+    match format {
+        StatusFormat::Plain => {
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+        StatusFormat::Waybar => {
+            let status = WaybarStatus {
+                text: stashes.len().to_string(),
+                tooltip: if lines.is_empty() {
+                    "No stashes".to_owned()
+                } else {
+                    lines.join("\n")
+                },
+                class: if stashes.is_empty() {
+                    "empty"
+                } else {
+                    "stashed"
+                }
+                .to_owned(),
+            };
+            println!("{}", serde_json::to_string(&status)?);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn follow_status(format: StatusFormat) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+
+    let window_tx = tx.clone();
+    thread::spawn(move || {
+        let mut listener = EventListener::new();
+        let opened_tx = window_tx.clone();
+        listener.add_window_opened_handler(move |_| {
+            let _ = opened_tx.send(());
+        });
+        let closed_tx = window_tx.clone();
+        listener.add_window_closed_handler(move |_| {
+            let _ = closed_tx.send(());
+        });
+        listener.add_window_moved_handler(move |_| {
+            let _ = window_tx.send(());
+        });
+        let _ = listener.start_listener();
+    });
+
+    // There is no portable file watcher in std, so poll the store instead.
+    thread::spawn(move || {
+        let mut last = StashedInstance::store_fingerprint();
+        loop {
+            thread::sleep(STORE_POLL_INTERVAL);
+            let current = StashedInstance::store_fingerprint();
+            if current != last {
+                last = current;
+                if tx.send(()).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    print_status(format)?;
+    for _ in rx.iter() {
+        print_status(format)?;
+    }
+
+    Ok(())
+}
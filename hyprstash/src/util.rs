@@ -1,12 +1,21 @@
 use super::*;
 
+use std::time::Duration;
+
+// Every dispatch goes through here, so that `-vv` shows each one.
+pub fn dispatch(dispatch: DispatchType) -> hyprland::Result<()> {
+    timed("dispatch", format!("{:?}", dispatch), || {
+        Dispatch::call(dispatch)
+    })
+}
+
 pub fn move_clients_to_workspace(
     target: WorkspaceId,
     clients: &[Address],
 ) -> Option<DispatchError> {
     let mut errors = vec![];
     for client in clients.iter() {
-        let res = Dispatch::call(DispatchType::MoveToWorkspaceSilent(
+        let res = dispatch(DispatchType::MoveToWorkspaceSilent(
             WorkspaceIdentifierWithSpecial::Id(target),
             Some(WindowIdentifier::Address(client.clone())),
         ));
@@ -22,10 +31,114 @@ pub fn move_clients_to_workspace(
     }
 }
 
+pub fn close_clients(clients: &[Address]) -> Option<DispatchError> {
+    let mut errors = vec![];
+    for client in clients.iter() {
+        let res = dispatch(DispatchType::CloseWindow(WindowIdentifier::Address(
+            client.clone(),
+        )));
+        if let Err(error) = res {
+            errors.push(error);
+        }
+    }
+
+    if errors.is_empty() {
+        None
+    } else {
+        Some(DispatchError(errors))
+    }
+}
+
+pub fn next_free_workspace(data: &Data, taken: &HashSet<WorkspaceId>) -> WorkspaceId {
+    (1..)
+        .find(|workspace| !data.has_workspace(*workspace) && !taken.contains(workspace))
+        .unwrap()
+}
+
+// Accepts durations like `45s`, `30m`, `8h`, `2d` or `1h30m`.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for c in input.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(format!("unknown duration unit '{}'", c)),
+        };
+        if number.is_empty() {
+            return Err(format!("missing number before '{}'", c));
+        }
+        let value = number
+            .parse::<u64>()
+            .map_err(|_| format!("duration '{}' is too long", input))?;
+        total = value
+            .checked_mul(unit)
+            .and_then(|value| total.checked_add(value))
+            .ok_or_else(|| format!("duration '{}' is too long", input))?;
+        number.clear();
+    }
+
+    if !number.is_empty() || total == 0 {
+        return Err(format!("invalid duration '{}'", input));
+    }
+
+    Ok(Duration::from_secs(total))
+}
+
 pub fn move_workspace_to_monitor(workspace: WorkspaceId, monitor: MonitorId) -> Result<()> {
-    Dispatch::call(DispatchType::MoveWorkspaceToMonitor(
+    dispatch(DispatchType::MoveWorkspaceToMonitor(
         WorkspaceIdentifier::Id(workspace),
         MonitorIdentifier::Id(monitor),
     ))?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("45s"), Ok(Duration::from_secs(45)));
+        assert_eq!(parse_duration("8h"), Ok(Duration::from_secs(8 * 60 * 60)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(90 * 60)));
+        assert_eq!(
+            parse_duration("2d1h1m1s"),
+            Ok(Duration::from_secs(2 * 86400 + 3600 + 60 + 1))
+        );
+        assert_eq!(parse_duration(" 30m "), Ok(Duration::from_secs(30 * 60)));
+    }
+
+    #[test]
+    fn malformed_durations() {
+        for input in ["", "0s", "30", "1h30", "h", "1w", "1.5h", "-1h", "1 h"] {
+            assert!(
+                parse_duration(input).is_err(),
+                "{:?} should be rejected",
+                input
+            );
+        }
+        assert_eq!(
+            parse_duration("1w"),
+            Err("unknown duration unit 'w'".to_owned())
+        );
+    }
+
+    #[test]
+    fn overflowing_durations() {
+        let too_long = Err("duration '99999999999999999999s' is too long".to_owned());
+        assert_eq!(parse_duration("99999999999999999999s"), too_long);
+        assert!(parse_duration(&format!("{}d", u64::MAX / 86400 + 1)).is_err());
+        assert!(parse_duration(&format!("{}s{}s", u64::MAX, 1)).is_err());
+        assert_eq!(
+            parse_duration(&format!("{}s", u64::MAX)),
+            Ok(Duration::from_secs(u64::MAX))
+        );
+    }
+}
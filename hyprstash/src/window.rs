@@ -0,0 +1,213 @@
+use super::*;
+
+use hyprland::{
+    ctl::set_prop::{self, PropType},
+    data::Client,
+};
+
+// Opacity isn't part of `clients`, every window needs its own `getprop`s.
+const OPACITY_PROPS: [&str; 4] = [
+    "alpha",
+    "alphaoverride",
+    "alphainactive",
+    "alphainactiveoverride",
+];
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum WindowProperty {
+    /// Pinned floating windows
+    Pinned,
+    /// Fullscreen and maximized windows
+    Fullscreen,
+    /// The fullscreen state the program itself is told about
+    FakeFullscreen,
+    /// Opacity set by `setprop` or window rules
+    Opacity,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct WindowState {
+    pub pinned: bool,
+    // Hyprland's fullscreen modes, 0 being a normal window.
+    pub fullscreen: u8,
+    pub fullscreen_client: u8,
+    pub alpha: Option<f32>,
+    pub alpha_override: bool,
+    pub alpha_inactive: Option<f32>,
+    pub alpha_inactive_override: bool,
+}
+
+impl WindowState {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// Hyprland versions without `getprop` simply don't get opacity restored.
+pub fn window_states(clients: &[&Client]) -> Vec<WindowState> {
+    let commands = clients
+        .iter()
+        .flat_map(|client| {
+            OPACITY_PROPS
+                .iter()
+                .map(move |prop| format!("j/getprop address:{} {}", client.address, prop))
+        })
+        .collect::<Vec<_>>();
+    let values = if commands.is_empty() {
+        vec![]
+    } else {
+        let commands = commands.iter().map(String::as_str).collect::<Vec<_>>();
+        batch_request(&commands).unwrap_or_default()
+    };
+
+    clients
+        .iter()
+        .enumerate()
+        .map(|(idx, client)| {
+            let prop = |offset: usize| {
+                values
+                    .get(idx * OPACITY_PROPS.len() + offset)
+                    .map(|value| &value[OPACITY_PROPS[offset]])
+            };
+            let alpha = |offset| {
+                prop(offset)
+                    .and_then(|value| value.as_f64())
+                    .map(|alpha| alpha as f32)
+                    .filter(|alpha| *alpha != 1.0)
+            };
+            let flag = |offset| {
+                prop(offset)
+                    .and_then(|value| value.as_bool().or(value.as_i64().map(|value| value != 0)))
+                    .unwrap_or(false)
+            };
+
+            WindowState {
+                pinned: client.pinned,
+                fullscreen: client.fullscreen as u8,
+                fullscreen_client: client.fullscreen_client as u8,
+                alpha: alpha(0),
+                alpha_override: flag(1),
+                alpha_inactive: alpha(2),
+                alpha_inactive_override: flag(3),
+            }
+        })
+        .collect()
+}
+
+// Pinned windows show on every workspace, so they have to be unpinned to be stashed.
+pub fn unpin_clients(clients: &[&Client]) -> Option<DispatchError> {
+    let mut errors = vec![];
+    for client in clients.iter().filter(|client| client.pinned) {
+        let res = dispatch(DispatchType::TogglePinWindow(WindowIdentifier::Address(
+            client.address.clone(),
+        )));
+        if let Err(error) = res {
+            errors.push(error);
+        }
+    }
+
+    if errors.is_empty() {
+        None
+    } else {
+        Some(DispatchError(errors))
+    }
+}
+
+// `fullscreenstate` only acts on the focused window, -1 leaves a mode as it is.
+fn fullscreen_state(state: &WindowState, skip: &HashSet<WindowProperty>) -> Option<String> {
+    let internal = (!skip.contains(&WindowProperty::Fullscreen)).then_some(state.fullscreen);
+    let client = if skip.contains(&WindowProperty::FakeFullscreen) {
+        internal
+    } else {
+        Some(state.fullscreen_client)
+    };
+    if internal.is_none() && client.is_none() {
+        return None;
+    }
+
+    let mode = |mode: Option<u8>| mode.map_or("-1".to_owned(), |mode| mode.to_string());
+    Some(format!("{} {}", mode(internal), mode(client)))
+}
+
+pub fn restore_window_states(
+    data: &Data,
+    clients: &[&StashedClient],
+    skip: &HashSet<WindowProperty>,
+) -> Option<DispatchError> {
+    let mut errors = vec![];
+    let mut refocus = false;
+    for stashed in clients {
+        let Some(client) = data.client(&stashed.address) else {
+            continue;
+        };
+        let state = &stashed.state;
+        let window = || WindowIdentifier::Address(stashed.address.clone());
+        let ident = || format!("address:{}", stashed.address);
+
+        if !skip.contains(&WindowProperty::Pinned)
+            && state.pinned
+            && !client.pinned
+            && let Err(error) = dispatch(DispatchType::TogglePinWindow(window()))
+        {
+            errors.push(error);
+        }
+
+        let fullscreen_changed = state.fullscreen != client.fullscreen as u8
+            || state.fullscreen_client != client.fullscreen_client as u8;
+        if fullscreen_changed && let Some(args) = fullscreen_state(state, skip) {
+            refocus = true;
+            let res = dispatch(DispatchType::FocusWindow(window()))
+                .and_then(|_| dispatch(DispatchType::Custom("fullscreenstate", &args)));
+            if let Err(error) = res {
+                errors.push(error);
+            }
+        }
+
+        if !skip.contains(&WindowProperty::Opacity) {
+            let mut props = vec![];
+            if let Some(alpha) = state.alpha {
+                props.push(PropType::Alpha(alpha, false));
+            }
+            if state.alpha_override {
+                props.push(PropType::AlphaOverride(true, false));
+            }
+            if let Some(alpha) = state.alpha_inactive {
+                props.push(PropType::AlphaInactive(alpha, false));
+            }
+            if state.alpha_inactive_override {
+                props.push(PropType::AlphaInactiveOverride(true, false));
+            }
+            for prop in props {
+                let request = format!("{} {}", ident(), prop);
+                if let Err(error) =
+                    timed("setprop", request, || set_prop::call(ident(), prop, false))
+                {
+                    errors.push(error);
+                }
+            }
+        }
+    }
+
+    // Fullscreen needed focus, hand it back to whatever had it.
+    if refocus
+        && let Some(focused) = data
+            .clients
+            .iter()
+            .find(|client| client.focus_history_id == 0)
+    {
+        let res = dispatch(DispatchType::FocusWindow(WindowIdentifier::Address(
+            focused.address.clone(),
+        )));
+        if let Err(error) = res {
+            errors.push(error);
+        }
+    }
+
+    if errors.is_empty() {
+        None
+    } else {
+        Some(DispatchError(errors))
+    }
+}